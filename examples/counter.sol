@@ -0,0 +1,10 @@
+pragma solidity >=0.4.24;
+
+contract Counter {
+
+    uint256 public count;
+
+    function increment() public {
+        count += 1;
+    }
+}
@@ -0,0 +1,10 @@
+pragma solidity >=0.6.5;
+
+contract Immutable {
+
+    uint256 public immutable value;
+
+    constructor(uint256 _value) public {
+        value = _value;
+    }
+}
@@ -0,0 +1,8 @@
+pragma solidity >=0.4.24;
+
+contract Reverts {
+
+    constructor() public {
+        require(false, "reverts on deployment");
+    }
+}
@@ -4,11 +4,25 @@ pub fn cli() -> Command<'static> {
     Command::new("morge")
         .about("A batch of contracts deployment CLI, currently support eth、goerli、kovan、rinkeby、ropsten、
         polygon、polygon-mumbai、fantom、fantom-testnet、bsc、bsc-testnet、arbitrum、arbitrum-testnet、
-        optimism、optimism-kovan、avalanche、avalanche-fuji chains.")
+        optimism、optimism-kovan、avalanche、avalanche-fuji chains, and user-defined ones.")
         .subcommand_required(true)
         .arg_required_else_help(true)
         .allow_external_subcommands(true)
         .allow_invalid_utf8_for_external_subcommands(true)
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .global(true)
+                .help("the config file, its directory is the project root"),
+        )
+        .arg(
+            Arg::with_name("root")
+                .long("root")
+                .takes_value(true)
+                .global(true)
+                .help("the project root, found upward from the current directory if not set"),
+        )
         .subcommand(Command::new("init").about("init deploy config"))
         .subcommand(
             Command::new("add")
@@ -24,7 +38,7 @@ pub fn cli() -> Command<'static> {
                     Arg::with_name("args")
                         .long("args")
                         .takes_value(true)
-                        .help("Five less than your favorite number")
+                        .help("constructor args by position or as name=value, [a, b] arrays, (a, b) tuples, amounts as 1.5 ether and @file:path")
                         .multiple_values(true),
                 )
                 .arg_required_else_help(true),
@@ -43,12 +57,13 @@ pub fn cli() -> Command<'static> {
         )
         .subcommand(
             Command::new("set")
-                .about("set rpc url and private key")
+                .about("set rpc url and private key, or the settings of a network profile")
                 .arg(
                     Arg::with_name("rpc-url")
                         .short('u')
                         .long("rpc-url")
                         .takes_value(true)
+                        .required_unless_present("network")
                         .help("set rpc url"),
                 )
                 .arg(
@@ -56,30 +71,413 @@ pub fn cli() -> Command<'static> {
                         .short('k')
                         .long("private-key")
                         .takes_value(true)
-                        .help("set private key"),
+                        .required_unless_present("network")
+                        .help("set private key, or env:NAME to read it from the env"),
+                )
+                .arg(network_arg())
+                .arg(
+                    Arg::with_name("expected-chain-id")
+                        .long("expected-chain-id")
+                        .takes_value(true)
+                        .requires("network")
+                        .help("chain id the rpc url must be on, checked before sending anything"),
+                )
+                .arg(
+                    Arg::with_name("confirmations")
+                        .long("confirmations")
+                        .takes_value(true)
+                        .requires("network")
+                        .help("blocks to wait before marking a contract deployed, the chain ones if not set"),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .takes_value(true)
+                        .requires("network")
+                        .help("seconds to wait for a deployment to be confirmed"),
+                )
+                .arg(
+                    Arg::with_name("max-retries")
+                        .long("max-retries")
+                        .takes_value(true)
+                        .requires("network")
+                        .help("times to retry rpc requests failed with rate limits or timeouts"),
+                )
+                .arg(
+                    Arg::with_name("fallback-rpc-url")
+                        .long("fallback-rpc-url")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .requires("network")
+                        .help("rpc url to fail over to when the rpc url is down, can be repeated"),
+                )
+                .arg(
+                    Arg::with_name("quorum")
+                        .long("quorum")
+                        .takes_value(true)
+                        .requires("network")
+                        .help("rpc urls which must agree on chain id, nonce, receipt and code reads"),
+                )
+                .arg(
+                    Arg::with_name("explorer-url")
+                        .long("explorer-url")
+                        .takes_value(true)
+                        .requires("network")
+                        .help("explorer api url, the etherscan family one of the chain if not set"),
+                )
+                .arg(
+                    Arg::with_name("explorer-api-key")
+                        .long("explorer-api-key")
+                        .takes_value(true)
+                        .requires("network")
+                        .help("explorer api key, read from the env of the chain as ETHERSCAN_API_KEY if not set"),
+                )
+                .arg(
+                    Arg::with_name("local")
+                        .long("local")
+                        .requires("network")
+                        .help("deploy the network to an anvil node spawned for the deployment"),
+                )
+                .args(anvil_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("set-chain")
+                .about("add or replace a user-defined chain, a built-in one of the same name or chain id is replaced")
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .takes_value(true)
+                        .required(true)
+                        .help("chain name, as given to verify --chain"),
+                )
+                .arg(
+                    Arg::with_name("chain-id")
+                        .long("chain-id")
+                        .takes_value(true)
+                        .required(true)
+                        .help("chain id"),
+                )
+                .arg(
+                    Arg::with_name("symbol")
+                        .long("symbol")
+                        .takes_value(true)
+                        .default_value("ETH")
+                        .help("symbol of the native currency"),
+                )
+                .arg(
+                    Arg::with_name("explorer-url")
+                        .long("explorer-url")
+                        .takes_value(true)
+                        .help("etherscan family explorer api url"),
+                )
+                .arg(
+                    Arg::with_name("api-key-env")
+                        .long("api-key-env")
+                        .takes_value(true)
+                        .help("env of the explorer api key, as BASESCAN_API_KEY"),
+                )
+                .arg(
+                    Arg::with_name("eip1559")
+                        .long("eip1559")
+                        .help("the chain supports EIP-1559 transactions"),
+                )
+                .arg(
+                    Arg::with_name("confirmations")
+                        .long("confirmations")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("blocks to wait before marking a contract deployed, unless the network sets them"),
                 )
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("verify")
-                .about("verify contract state")
+                .about("verify transaction status by the rpc url of the network, or the explorer of the chain")
                 .arg(
                     Arg::with_name("chain")
                         .short('c')
                         .long("chain")
                         .takes_value(true)
-                        .help("provide chainnet name"),
+                        .conflicts_with("network")
+                        .help("provide chainnet name, verify by its explorer"),
                 )
                 .arg(
                     Arg::with_name("tx")
                         .short('t')
                         .long("tx")
                         .takes_value(true)
+                        .required_unless_present("all")
                         .help("provide the transaction hash"),
                 )
+                .arg(network_arg())
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .conflicts_with_all(&["tx", "chain"])
+                        .help("verify every deployment of the network recorded in the journal"),
+                )
+                .arg(
+                    Arg::with_name("concurrency")
+                        .long("concurrency")
+                        .takes_value(true)
+                        .requires("all")
+                        .default_value("4")
+                        .help("deployments to verify at a time"),
+                )
                 .arg_required_else_help(true),
         )
-        .subcommand(Command::new("deploy").about("the chain to deploy"))
+        .subcommand(
+            Command::new("verify-contract")
+                .about("verify the source of a deployed contract on the block explorer")
+                .arg(
+                    Arg::with_name("contract")
+                        .short('c')
+                        .long("contract")
+                        .takes_value(true)
+                        .required(true)
+                        .help("specify the contract file and name, as x.sol:x"),
+                )
+                .arg(network_arg())
+                .args(verify_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("deploy")
+                .about("the chain to deploy")
+                .arg(network_arg().help(
+                    "the network profile, or several separated by commas which are deployed to concurrently",
+                ))
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .conflicts_with_all(&["network", "safe", "sign-only", "rehearse"])
+                        .help("deploy to every network profile concurrently"),
+                )
+                .arg(
+                    Arg::with_name("safe")
+                        .long("safe")
+                        .takes_value(true)
+                        .help("export a Safe transaction builder batch file instead of broadcasting"),
+                )
+                .arg(
+                    Arg::with_name("safe-address")
+                        .long("safe-address")
+                        .takes_value(true)
+                        .requires("safe")
+                        .help("the Safe address which will execute the batch"),
+                )
+                .arg(
+                    Arg::with_name("chain-id")
+                        .long("chain-id")
+                        .takes_value(true)
+                        .help("chain id of the target chain, queried from rpc url if not set"),
+                )
+                .arg(
+                    Arg::with_name("salt")
+                        .long("salt")
+                        .takes_value(true)
+                        .requires("safe")
+                        .help("deploy with CREATE2 and the given 32 bytes salt"),
+                )
+                .args(replace_args())
+                .arg(
+                    Arg::with_name("sign-only")
+                        .long("sign-only")
+                        .conflicts_with("safe")
+                        .requires_all(&["nonce", "chain-id", "gas-price", "out"])
+                        .help("sign the deployment transactions offline without broadcasting"),
+                )
+                .arg(
+                    Arg::with_name("nonce")
+                        .long("nonce")
+                        .takes_value(true)
+                        .requires("sign-only")
+                        .help("nonce of the first deployment transaction"),
+                )
+                .arg(
+                    Arg::with_name("gas-price")
+                        .long("gas-price")
+                        .takes_value(true)
+                        .requires("sign-only")
+                        .help("gas price in gwei"),
+                )
+                .arg(
+                    Arg::with_name("gas-limit")
+                        .long("gas-limit")
+                        .takes_value(true)
+                        .requires("sign-only")
+                        .default_value("3000000")
+                        .help("gas limit of every deployment transaction"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .short('o')
+                        .long("out")
+                        .takes_value(true)
+                        .requires("sign-only")
+                        .help("file to write the signed transactions"),
+                )
+                .arg(
+                    Arg::with_name("rehearse")
+                        .long("rehearse")
+                        .conflicts_with_all(&["safe", "sign-only"])
+                        .help("rehearse the deployment on an anvil fork of the network"),
+                )
+                .arg(
+                    Arg::with_name("verify")
+                        .long("verify")
+                        .conflicts_with_all(&["safe", "sign-only", "rehearse"])
+                        .help("verify the source of the deployed contracts on the block explorer"),
+                )
+                .args(verify_args())
+                .arg(
+                    Arg::with_name("fork-block-number")
+                        .long("fork-block-number")
+                        .takes_value(true)
+                        .requires("rehearse")
+                        .help("block to fork at, the latest one if not set"),
+                ),
+        )
+        .subcommand(
+            Command::new("cancel")
+                .about("replace a stuck transaction with a 0-value self transfer")
+                .arg(
+                    Arg::with_name("nonce")
+                        .long("nonce")
+                        .takes_value(true)
+                        .required(true)
+                        .help("nonce of the stuck transaction"),
+                )
+                .arg(network_arg())
+                .args(replace_args()),
+        )
+        .subcommand(
+            Command::new("broadcast")
+                .about("broadcast signed transactions produced by deploy --sign-only")
+                .arg(
+                    Arg::with_name("file")
+                        .takes_value(true)
+                        .required(true)
+                        .help("the signed transactions file"),
+                )
+                .arg(network_arg()),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("check the code of the deployed contracts matches the local compilation")
+                .arg(network_arg()),
+        )
+        .subcommand(
+            Command::new("check-config")
+                .about("validate the config offline and report every problem of it"),
+        )
+        .subcommand(
+            Command::new("node")
+                .about("start a local anvil node which keeps its state between runs")
+                .arg(
+                    Arg::with_name("port")
+                        .short('p')
+                        .long("port")
+                        .takes_value(true)
+                        .help("port of the local node, 8545 if not set"),
+                ),
+        )
         .subcommand(Command::new("clean").about("clean the deploy cache"))
         .subcommand(Command::new("list").about("list the added contract files"))
 }
+
+fn network_arg() -> Arg<'static> {
+    Arg::with_name("network")
+        .short('n')
+        .long("network")
+        .takes_value(true)
+        .help("the network profile, top level rpc url and private key are used if not set")
+}
+
+// options to verify contract sources on the block explorer
+fn verify_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::with_name("chain")
+            .long("chain")
+            .takes_value(true)
+            .help("explorer chain name, found by the chain id of the deployment if not set"),
+        Arg::with_name("code-format")
+            .long("code-format")
+            .takes_value(true)
+            .possible_values(["single-file", "standard-json"])
+            .default_value("standard-json")
+            .help("submit the contract file only, or with the files it imports"),
+        Arg::with_name("compiler-version")
+            .long("compiler-version")
+            .takes_value(true)
+            .help("solc version as v0.8.15+commit.e14f2714, the local solc one if not set"),
+    ]
+}
+
+// options of the anvil node of a local network
+fn anvil_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::with_name("port")
+            .long("port")
+            .takes_value(true)
+            .requires("network")
+            .help("port of the local anvil node"),
+        Arg::with_name("chain-id")
+            .long("chain-id")
+            .takes_value(true)
+            .requires("network")
+            .help("chain id of the local anvil node"),
+        Arg::with_name("block-time")
+            .long("block-time")
+            .takes_value(true)
+            .requires("network")
+            .help("seconds between blocks of the local anvil node"),
+        Arg::with_name("accounts")
+            .long("accounts")
+            .takes_value(true)
+            .requires("network")
+            .help("number of accounts of the local anvil node"),
+        Arg::with_name("mnemonic")
+            .long("mnemonic")
+            .takes_value(true)
+            .requires("network")
+            .help("mnemonic of the local anvil node accounts"),
+        Arg::with_name("load-state")
+            .long("load-state")
+            .takes_value(true)
+            .requires("network")
+            .help("file to load the local anvil node state from on start"),
+        Arg::with_name("fork-url")
+            .long("fork-url")
+            .takes_value(true)
+            .requires("network")
+            .help("rpc url of the chain the local anvil node forks"),
+        Arg::with_name("fork-block-number")
+            .long("fork-block-number")
+            .takes_value(true)
+            .requires("network")
+            .help("block the local anvil node forks at"),
+    ]
+}
+
+// options to replace transactions which are not mined in time
+fn replace_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::with_name("replace-timeout")
+            .long("replace-timeout")
+            .takes_value(true)
+            .default_value("120")
+            .help("seconds to wait before replacing a transaction with a higher gas price"),
+        Arg::with_name("max-replacements")
+            .long("max-replacements")
+            .takes_value(true)
+            .default_value("3")
+            .help("max times to replace a transaction before giving up"),
+        Arg::with_name("fee-bump")
+            .long("fee-bump")
+            .takes_value(true)
+            .default_value("15")
+            .help("percentage to bump the gas price of each replacement, at least 10"),
+    ]
+}
@@ -0,0 +1,135 @@
+use ethers::{core::abi::Contract as Abi, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::contract::{CompilerSettings, ContractInfo};
+use crate::project::project_file;
+use crate::utils::fs::write_atomic;
+use crate::INIT_ARTIFACTS;
+
+// compilation output of a contract, kept apart from the config so that
+// recompiling never touches it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub contract: String,
+    pub name: String,
+    pub abi: Abi,
+    pub bytecode: Bytes,
+    pub runtime_bytecode: Bytes,
+    // start and length of the immutables in the runtime bytecode
+    #[serde(default)]
+    pub immutables: Vec<(usize, usize)>,
+    // the compiler settings it was compiled with
+    pub compiler: CompilerSettings,
+}
+
+// artifact file of the contract relative to the project root, keyed by the
+// contract path, as .morge/artifacts/examples/contract.sol/SimpleStorage.json
+pub fn artifact_path(contract: &str, name: &str) -> String {
+    let mut path = Path::new(INIT_ARTIFACTS).to_path_buf();
+    // contracts outside the project are kept by their absolute path
+    for component in Path::new(contract).components() {
+        if let Component::Normal(part) = component {
+            path.push(part);
+        }
+    }
+    path.join(format!("{}.json", name))
+        .to_string_lossy()
+        .to_string()
+}
+
+impl Artifact {
+    pub fn of(contract: &ContractInfo) -> Self {
+        Artifact {
+            contract: contract.contract.clone(),
+            name: contract.name.clone(),
+            abi: contract.abi.clone(),
+            bytecode: contract.bytecode.clone(),
+            runtime_bytecode: contract.runtime_bytecode.clone(),
+            immutables: contract.immutables.clone(),
+            compiler: contract.compiler.clone(),
+        }
+    }
+
+    // save to the artifact file, relative to the project root
+    pub fn save(&self, path: &str) -> eyre::Result<()> {
+        let path = project_file(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(&path, &serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// the artifact saved to the file, None if the contract was never compiled
+pub fn restore_artifact(path: &str) -> eyre::Result<Option<Artifact>> {
+    let path = project_file(path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = fs::read_to_string(path)?;
+    Ok(Some(serde_json::from_str(&json)?))
+}
+
+// remove the artifact file, if any
+pub fn remove_artifact(path: &str) -> eyre::Result<()> {
+    let path = project_file(path);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::TempRoot;
+
+    #[test]
+    fn test_artifact_path() {
+        assert_eq!(
+            artifact_path("examples/contract.sol", "SimpleStorage"),
+            ".morge/artifacts/examples/contract.sol/SimpleStorage.json"
+        );
+        // contracts of the same file name in other directories are kept apart
+        assert_ne!(
+            artifact_path("src/a/Token.sol", "Token"),
+            artifact_path("src/b/Token.sol", "Token")
+        );
+        assert_eq!(
+            artifact_path("/outside/Token.sol", "Token"),
+            ".morge/artifacts/outside/Token.sol/Token.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compile_writes_artifact() {
+        // given
+        let root = TempRoot::new();
+        let mut contract = ContractInfo::new("examples/contract.sol:SimpleStorage".into(), vec![]);
+
+        // when
+        contract.compile().await.unwrap();
+
+        // then the config refers to the artifact
+        let file = contract.artifact.clone().unwrap();
+        assert!(root.path.join(&file).exists());
+        let artifact = restore_artifact(&file).unwrap().unwrap();
+        assert_eq!(artifact.bytecode, contract.bytecode);
+        assert_eq!(artifact.abi, contract.abi);
+
+        // and the artifact is loaded instead of compiling again
+        let saved = Artifact {
+            runtime_bytecode: vec![0u8].into(),
+            ..artifact
+        };
+        saved.save(&file).unwrap();
+        let mut loaded = ContractInfo::new("examples/contract.sol:SimpleStorage".into(), vec![]);
+        loaded.load().await.unwrap();
+        assert_eq!(loaded.runtime_bytecode, saved.runtime_bytecode);
+        remove_artifact(&file).unwrap();
+        assert!(restore_artifact(&file).unwrap().is_none());
+    }
+}
@@ -0,0 +1,218 @@
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{restore_cfg, DEFAULT_CONFIRMATIONS};
+
+// name, chain id, native symbol, explorer api url, env of the explorer api key
+// and EIP-1559 support of the chains known without being configured
+#[rustfmt::skip]
+static BUILTIN_CHAINS: [(&str, u64, &str, &str, &str, bool); 17] = [
+    ("eth", 1, "ETH", "https://api.etherscan.io/api", "ETHERSCAN_API_KEY", true),
+    ("ropsten", 3, "ETH", "https://api-ropsten.etherscan.io/api", "ETHERSCAN_API_KEY", true),
+    ("rinkeby", 4, "ETH", "https://api-rinkeby.etherscan.io/api", "ETHERSCAN_API_KEY", true),
+    ("kovan", 42, "ETH", "https://api-kovan.etherscan.io/api", "ETHERSCAN_API_KEY", true),
+    ("goerli", 5, "ETH", "https://api-goerli.etherscan.io/api", "ETHERSCAN_API_KEY", true),
+    ("polygon", 137, "MATIC", "https://api.polygonscan.com/api", "POLYGONSCAN_API_KEY", true),
+    ("polygon-mumbai", 80001, "MATIC", "https://api-testnet.polygonscan.com/api", "POLYGONSCAN_API_KEY", true),
+    ("fantom", 250, "FTM", "https://api.ftmscan.com/api", "FTMSCAN_API_KEY", false),
+    ("fantom-testnet", 4002, "FTM", "https://api-testnet.ftmscan.com/api", "FTMSCAN_API_KEY", false),
+    ("bsc", 56, "BNB", "https://api.bscscan.com/api", "BSCSCAN_API_KEY", false),
+    ("bsc-testnet", 97, "BNB", "https://api-testnet.bscscan.com/api", "BSCSCAN_API_KEY", false),
+    ("arbitrum", 42161, "ETH", "https://api.arbiscan.io/api", "ARBISCAN_API_KEY", true),
+    ("arbitrum-testnet", 421611, "ETH", "https://api-testnet.arbiscan.io/api", "ARBISCAN_API_KEY", true),
+    ("optimism", 10, "ETH", "https://api-optimistic.etherscan.io/api", "OPTIMISTIC_ETHERSCAN_API_KEY", true),
+    ("optimism-kovan", 69, "ETH", "https://api-kovan-optimistic.etherscan.io/api", "OPTIMISTIC_ETHERSCAN_API_KEY", true),
+    ("avalanche", 43114, "AVAX", "https://api.snowtrace.io/api", "SNOWTRACE_API_KEY", true),
+    ("avalanche-fuji", 43113, "AVAX", "https://api-testnet.snowtrace.io/api", "SNOWTRACE_API_KEY", true),
+];
+
+// a chain deployed to, verified on and reported by name
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChainInfo {
+    pub name: String,
+    pub chain_id: u64,
+    // symbol of the native currency, fees and balances are reported in it
+    #[serde(default = "default_symbol")]
+    pub symbol: String,
+    // etherscan family explorer api url
+    #[serde(default)]
+    pub explorer_url: Option<String>,
+    // env of the explorer api key, as ETHERSCAN_API_KEY
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default)]
+    pub eip1559: bool,
+    // blocks to wait before marking a contract deployed, unless the network sets them
+    #[serde(default = "default_confirmations")]
+    pub confirmations: usize,
+}
+
+fn default_symbol() -> String {
+    "ETH".to_string()
+}
+
+fn default_confirmations() -> usize {
+    DEFAULT_CONFIRMATIONS
+}
+
+impl ChainInfo {
+    pub fn new(name: &str, chain_id: u64) -> Self {
+        ChainInfo {
+            name: name.to_string(),
+            chain_id,
+            symbol: default_symbol(),
+            explorer_url: None,
+            api_key_env: None,
+            eip1559: false,
+            confirmations: default_confirmations(),
+        }
+    }
+
+    // the wei amount in the native currency, as 1.5 ETH
+    pub fn amount(&self, wei: U256) -> String {
+        let unit = U256::exp10(18);
+        let fraction = format!("{:0>18}", (wei % unit).to_string());
+        match fraction.trim_end_matches('0') {
+            "" => format!("{} {}", wei / unit, self.symbol),
+            fraction => format!("{}.{} {}", wei / unit, fraction, self.symbol),
+        }
+    }
+}
+
+// the built-in chains and the user-defined ones of the config
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainRegistry {
+    chains: Vec<ChainInfo>,
+}
+
+impl Default for ChainRegistry {
+    fn default() -> Self {
+        let chains = BUILTIN_CHAINS
+            .iter()
+            .map(
+                |(name, chain_id, symbol, explorer_url, api_key_env, eip1559)| ChainInfo {
+                    symbol: symbol.to_string(),
+                    explorer_url: Some(explorer_url.to_string()),
+                    api_key_env: Some(api_key_env.to_string()),
+                    eip1559: *eip1559,
+                    ..ChainInfo::new(name, *chain_id)
+                },
+            )
+            .collect();
+        ChainRegistry { chains }
+    }
+}
+
+impl ChainRegistry {
+    // a user-defined chain replaces the built-in one of the same name or chain id
+    pub fn new(custom: &[ChainInfo]) -> Self {
+        let mut registry = ChainRegistry::default();
+        for chain in custom {
+            registry
+                .chains
+                .retain(|item| item.name != chain.name && item.chain_id != chain.chain_id);
+            registry.chains.push(chain.clone());
+        }
+        registry
+    }
+
+    // the chains of the config, the built-in ones only if there is no config
+    pub fn load() -> Self {
+        restore_cfg()
+            .map(|cfg| ChainRegistry::new(&cfg.chains))
+            .unwrap_or_default()
+    }
+
+    pub fn chains(&self) -> &[ChainInfo] {
+        &self.chains
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&ChainInfo> {
+        self.chains.iter().find(|chain| chain.name == name)
+    }
+
+    pub fn by_chain_id(&self, chain_id: u64) -> Option<&ChainInfo> {
+        self.chains.iter().find(|chain| chain.chain_id == chain_id)
+    }
+
+    // the named chain, or the one of the chain id if no name is given
+    pub fn find(&self, name: Option<&str>, chain_id: u64) -> eyre::Result<Option<&ChainInfo>> {
+        match name {
+            Some(name) => self
+                .by_name(name)
+                .map(Some)
+                .ok_or_else(|| eyre::eyre!("Unknown chain {}, please set it with set-chain", name)),
+            None => Ok(self.by_chain_id(chain_id)),
+        }
+    }
+
+    // name and chain id of the chain for reports, as polygon (137)
+    pub fn describe(&self, chain_id: u64) -> String {
+        match self.by_chain_id(chain_id) {
+            Some(chain) => format!("{} ({})", chain.name, chain_id),
+            None => format!("chain id {}", chain_id),
+        }
+    }
+}
+
+// the wei amount in the native currency of the chain, in wei if it is unknown
+pub fn amount(chain: Option<&ChainInfo>, wei: U256) -> String {
+    match chain {
+        Some(chain) => chain.amount(wei),
+        None => format!("{} wei", wei),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_chains() {
+        let chains = ChainRegistry::default();
+        assert_eq!(chains.chains().len(), BUILTIN_CHAINS.len());
+        let polygon = chains.by_name("polygon").unwrap();
+        assert_eq!(polygon.chain_id, 137);
+        assert_eq!(polygon.symbol, "MATIC");
+        assert_eq!(polygon.confirmations, DEFAULT_CONFIRMATIONS);
+        assert_eq!(chains.by_chain_id(56).unwrap().name, "bsc");
+        assert!(!chains.by_chain_id(56).unwrap().eip1559);
+        assert_eq!(chains.describe(43114), "avalanche (43114)");
+        assert_eq!(chains.describe(31337), "chain id 31337");
+    }
+
+    #[test]
+    fn test_custom_chains() {
+        // given
+        let base = ChainInfo {
+            explorer_url: Some("https://api.basescan.org/api".into()),
+            api_key_env: Some("BASESCAN_API_KEY".into()),
+            eip1559: true,
+            confirmations: 3,
+            ..ChainInfo::new("base", 8453)
+        };
+        let mainnet = ChainInfo::new("mainnet", 1);
+
+        // when
+        let chains = ChainRegistry::new(&[base.clone(), mainnet.clone()]);
+
+        // then
+        assert_eq!(chains.by_name("base"), Some(&base));
+        assert_eq!(chains.by_chain_id(8453), Some(&base));
+        assert_eq!(chains.by_chain_id(1), Some(&mainnet));
+        assert!(chains.by_name("eth").is_none());
+        assert_eq!(chains.find(None, 8453).unwrap(), Some(&base));
+        assert_eq!(chains.find(Some("base"), 1).unwrap(), Some(&base));
+        assert!(chains.find(None, 31337).unwrap().is_none());
+        assert!(chains.find(Some("zkevm"), 1101).is_err());
+    }
+
+    #[test]
+    fn test_amount() {
+        let chain = ChainInfo::new("local", 31337);
+        assert_eq!(chain.amount(U256::exp10(18) * 3 / 2), "1.5 ETH");
+        assert_eq!(chain.amount(U256::exp10(18) * 2), "2 ETH");
+        assert_eq!(chain.amount(1.into()), "0.000000000000000001 ETH");
+        assert_eq!(amount(None, 21000.into()), "21000 wei");
+    }
+}
@@ -0,0 +1,141 @@
+// whether the on-chain code is the compiled runtime bytecode, the metadata hash
+// may differ and the immutables, given by their start and length as reported
+// by solc, are filled in at deployment
+pub fn bytecode_matches(onchain: &[u8], compiled: &[u8], immutables: &[(usize, usize)]) -> bool {
+    let onchain = strip_metadata(onchain);
+    let compiled = strip_metadata(compiled);
+    if compiled.is_empty() || onchain.len() != compiled.len() {
+        return false;
+    }
+
+    onchain
+        .iter()
+        .zip(compiled.iter())
+        .enumerate()
+        .all(|(idx, (a, b))| {
+            a == b
+                || immutables
+                    .iter()
+                    .any(|(start, length)| (*start..start + length).contains(&idx))
+        })
+}
+
+// solc appends the cbor encoded metadata and its 2 bytes length to the code
+fn strip_metadata(code: &[u8]) -> &[u8] {
+    if code.len() < 2 {
+        return code;
+    }
+    let len = u16::from_be_bytes([code[code.len() - 2], code[code.len() - 1]]) as usize;
+    match code.len().checked_sub(len + 2) {
+        // cbor map of 1 to 5 entries
+        Some(start) if (0xa1..=0xa5).contains(&code[start]) => &code[..start],
+        _ => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Network;
+    use crate::contract::ContractInfo;
+    use crate::nonce::{NonceManager, ReplaceOptions};
+    use crate::project::TempRoot;
+    use crate::utils::parse::get_provider;
+    use ethers::prelude::*;
+    use ethers::utils::Anvil;
+
+    #[test]
+    fn test_bytecode_matches() {
+        // PUSH32 0 of an immutable, PUSH32 0 of a constant, STOP with metadata
+        let mut compiled = vec![0x7f];
+        compiled.extend([0u8; 32]);
+        compiled.push(0x7f);
+        compiled.extend([0u8; 32]);
+        compiled.push(0);
+        let mut onchain = compiled.clone();
+        compiled.extend([0xa1, 0x00, 0x00, 0x00, 0x03]);
+        let immutables = [(1, 32)];
+
+        // the immutable is filled in and the metadata is different
+        onchain[5] = 0xff;
+        onchain.extend([0xa2, 0x01, 0x02, 0x00, 0x03]);
+        assert!(bytecode_matches(&onchain, &compiled, &immutables));
+
+        // the constant is not an immutable
+        onchain[40] = 0xff;
+        assert!(!bytecode_matches(&onchain, &compiled, &immutables));
+        onchain[40] = 0;
+        assert!(!bytecode_matches(&onchain, &compiled, &[]));
+        assert!(!bytecode_matches(&[], &compiled, &immutables));
+        assert!(!bytecode_matches(&compiled, &[], &immutables));
+    }
+
+    #[tokio::test]
+    async fn test_deployed_bytecode_matches() {
+        // given
+        let _root = TempRoot::new();
+        let anvil = Anvil::new().spawn();
+        let client = get_provider(Some(&anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+        let mut contract = ContractInfo::new(
+            "examples/contract.sol:SimpleStorage".to_string(),
+            vec!["value".into()],
+        );
+
+        // when
+        let receipt = contract.run(&mut manager).await.unwrap();
+        let code = manager
+            .client()
+            .get_code(receipt.contract_address.unwrap(), None)
+            .await
+            .unwrap();
+
+        // then
+        assert!(bytecode_matches(
+            code.as_ref(),
+            contract.runtime_bytecode.as_ref(),
+            &contract.immutables
+        ));
+        assert!(!bytecode_matches(
+            code.as_ref(),
+            contract.bytecode.as_ref(),
+            &contract.immutables
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_immutables_filled_in() {
+        // given
+        let _root = TempRoot::new();
+        let anvil = Anvil::new().spawn();
+        let client = get_provider(Some(&anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+        let mut contract =
+            ContractInfo::new("examples/immutable.sol:Immutable".into(), vec!["7".into()]);
+
+        // when
+        let receipt = contract.run(&mut manager).await.unwrap();
+        let code = manager
+            .client()
+            .get_code(receipt.contract_address.unwrap(), None)
+            .await
+            .unwrap();
+
+        // then only the immutable reported by solc may differ
+        assert_eq!(contract.immutables.len(), 1);
+        assert!(bytecode_matches(
+            code.as_ref(),
+            contract.runtime_bytecode.as_ref(),
+            &contract.immutables
+        ));
+        assert!(!bytecode_matches(
+            code.as_ref(),
+            contract.runtime_bytecode.as_ref(),
+            &[]
+        ));
+    }
+}
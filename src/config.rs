@@ -1,77 +1,572 @@
+use ethers::utils::AnvilInstance;
 use serde::{Deserialize, Serialize};
-use serde_json::Result;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
 use std::fs::File;
-use std::io::Write;
 use std::path::Path;
 
-use crate::contract::ContractInfo;
+use crate::artifact::remove_artifact;
+use crate::chain::{ChainInfo, ChainRegistry};
+use crate::contract::{CompilerSettings, ContractInfo};
+use crate::local::{AnvilOptions, DUMP_STATE_UNSUPPORTED, LOCAL_NETWORK};
+use crate::project::{config_file, project_file, relative_path, root};
+use crate::retry::DEFAULT_MAX_RETRIES;
 use crate::utils::fs::*;
-use crate::{INIT_CFG, INIT_PATH};
+use crate::{INIT_LOCK, INIT_SECRETS, LEGACY_CFG};
+
+// version of the config schema, configs of older versions are migrated on load
+pub static CONFIG_VERSION: u32 = 2;
 
 // init config file, include rpc url、private key and contracts.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
+    // 0 for the configs without version
+    #[serde(default)]
+    pub version: u32,
     pub rpc_url: Option<String>,
     pub pri_key: Option<String>,
+    #[serde(default)]
+    pub compiler: CompilerSettings,
+    #[serde(default)]
+    pub networks: BTreeMap<String, Network>,
+    // user-defined chains, added to or replacing the built-in ones
+    #[serde(default)]
+    pub chains: Vec<ChainInfo>,
+    #[serde(default)]
     pub contracts: Vec<ContractInfo>,
 }
 
-fn from_json(json: &str) -> Result<Config> {
-    serde_json::from_str(json)
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            rpc_url: None,
+            pri_key: None,
+            compiler: CompilerSettings::default(),
+            networks: BTreeMap::new(),
+            chains: vec![],
+            contracts: vec![],
+        }
+    }
+}
+
+// network profile, rpc url and private key fall back to the top level ones if not set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Network {
+    pub rpc_url: Option<String>,
+    // rpc urls to fail over to when the rpc url is down
+    #[serde(default)]
+    pub fallback_rpc_urls: Vec<String>,
+    pub pri_key: Option<String>,
+    // chain id the rpc url must be on, checked before sending anything
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    // blocks to wait before marking a contract deployed, the default ones of
+    // the chain if not set
+    #[serde(default)]
+    pub confirmations: Option<usize>,
+    // seconds to wait for a deployment to be confirmed
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    // times to retry rpc requests failed with rate limits or timeouts
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    // rpc urls which must agree on chain id, nonces and receipts, 0 to disable
+    #[serde(default)]
+    pub quorum: usize,
+    // deploy to an anvil node spawned with these options instead of the rpc url
+    #[serde(default)]
+    pub local: Option<AnvilOptions>,
+    // explorer api url, the etherscan family one of the chain if not set
+    #[serde(default)]
+    pub explorer_url: Option<String>,
+    // explorer api key, read from the env of the chain if not set
+    #[serde(default)]
+    pub explorer_api_key: Option<String>,
 }
 
-fn to_json(cfg: &Config) -> Result<String> {
-    serde_json::to_string_pretty(cfg)
+// fields to change of a network profile, the ones not set are kept
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkUpdate {
+    pub rpc_url: Option<String>,
+    pub fallback_rpc_urls: Option<Vec<String>>,
+    pub pri_key: Option<String>,
+    pub chain_id: Option<u64>,
+    pub confirmations: Option<usize>,
+    pub timeout: Option<u64>,
+    pub max_retries: Option<u32>,
+    pub quorum: Option<usize>,
+    pub local: Option<AnvilOptions>,
+    pub explorer_url: Option<String>,
+    pub explorer_api_key: Option<String>,
 }
 
-pub fn save(cfg: &Config) -> eyre::Result<()> {
-    if !is_existed(&INIT_PATH.into()) {
-        fs::create_dir(INIT_PATH)?;
+pub static DEFAULT_NETWORK: &str = "default";
+pub static DEFAULT_CONFIRMATIONS: usize = 1;
+
+fn default_timeout() -> u64 {
+    600
+}
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network {
+            rpc_url: None,
+            fallback_rpc_urls: vec![],
+            pri_key: None,
+            chain_id: None,
+            confirmations: None,
+            timeout: default_timeout(),
+            max_retries: default_max_retries(),
+            quorum: 0,
+            local: None,
+            explorer_url: None,
+            explorer_api_key: None,
+        }
+    }
+}
+
+impl Network {
+    // the rpc url followed by the fallback ones
+    pub fn rpc_urls(&self) -> Vec<String> {
+        self.rpc_url
+            .iter()
+            .chain(self.fallback_rpc_urls.iter())
+            .filter(|rpc_url| !rpc_url.is_empty())
+            .cloned()
+            .collect()
+    }
+
+    // blocks to wait on the chain, the network ones or the default ones of the chain
+    pub fn confirmations_on(&self, chain: Option<&ChainInfo>) -> usize {
+        self.confirmations
+            .or_else(|| chain.map(|chain| chain.confirmations))
+            .unwrap_or(DEFAULT_CONFIRMATIONS)
     }
-    let json = to_json(cfg);
-    let mut file = File::create(Path::new(INIT_CFG))?;
-    file.write_all(json?.as_bytes())?;
+
+    // the node must be on the chain the network declares, if it declares one
+    pub fn check_chain_id(&self, chain_id: u64) -> eyre::Result<()> {
+        match self.chain_id {
+            Some(expected) if expected != chain_id => Err(eyre::eyre!(
+                "Chain id mismatch, the network expects chain id {} but the rpc url is chain id {}",
+                expected,
+                chain_id
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // spawn the anvil node of a local network, None for the remote ones. The
+    // node is killed when dropped, so it never dumps its state
+    pub fn spawn_local(&self) -> eyre::Result<Option<AnvilInstance>> {
+        match &self.local {
+            Some(local) if local.dump_state.is_some() => Err(eyre::eyre!(
+                "{}, please run morge node to keep the chain state between runs",
+                DUMP_STATE_UNSUPPORTED
+            )),
+            Some(local) => Ok(Some(local.spawn())),
+            None => Ok(None),
+        }
+    }
+
+    fn update(&mut self, update: NetworkUpdate) {
+        if update.rpc_url.is_some() {
+            self.rpc_url = update.rpc_url;
+        }
+        if let Some(fallback_rpc_urls) = update.fallback_rpc_urls {
+            self.fallback_rpc_urls = fallback_rpc_urls;
+        }
+        if update.pri_key.is_some() {
+            self.pri_key = update.pri_key;
+        }
+        if update.chain_id.is_some() {
+            self.chain_id = update.chain_id;
+        }
+        if update.confirmations.is_some() {
+            self.confirmations = update.confirmations;
+        }
+        if let Some(timeout) = update.timeout {
+            self.timeout = timeout;
+        }
+        if let Some(max_retries) = update.max_retries {
+            self.max_retries = max_retries;
+        }
+        if let Some(quorum) = update.quorum {
+            self.quorum = quorum;
+        }
+        if let Some(local) = update.local {
+            self.local.get_or_insert_with(Default::default).merge(local);
+        }
+        if update.explorer_url.is_some() {
+            self.explorer_url = update.explorer_url;
+        }
+        if update.explorer_api_key.is_some() {
+            self.explorer_api_key = update.explorer_api_key;
+        }
+    }
+}
+
+fn from_toml(toml: &str) -> eyre::Result<Config> {
+    Ok(toml::from_str(toml)?)
+}
+
+// the tables are written after the plain values, as toml requires
+fn to_toml(cfg: &Config) -> eyre::Result<String> {
+    Ok(toml::to_string_pretty(&toml::Value::try_from(cfg)?)?)
+}
+
+// the keys are written to the git-ignored secrets file, morge.toml only keeps
+// the env:NAME references
+pub fn save(cfg: &Config) -> eyre::Result<()> {
+    create_project_dir()?;
+    let (cfg, secrets) = split_secrets(cfg);
+    write_atomic(&config_file(), &to_toml(&cfg)?)?;
+    write_atomic(
+        &project_file(INIT_SECRETS),
+        &toml::to_string_pretty(&secrets)?,
+    )?;
     Ok(())
 }
 
+// private keys and explorer api keys, kept out of morge.toml which is reviewed
+// and committed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Secrets {
+    #[serde(default)]
+    pri_key: Option<String>,
+    #[serde(default)]
+    networks: BTreeMap<String, NetworkSecrets>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct NetworkSecrets {
+    #[serde(default)]
+    pri_key: Option<String>,
+    #[serde(default)]
+    explorer_api_key: Option<String>,
+}
+
+// a secret may be given as env:NAME to be read from the env when it is used
+static ENV_PREFIX: &str = "env:";
+
+fn is_secret(value: &Option<String>) -> bool {
+    matches!(value, Some(value) if !value.is_empty() && !value.starts_with(ENV_PREFIX))
+}
+
+// the secret to print, only its last 4 characters, env:NAME as it is
+fn mask_secret(value: &Option<String>) -> String {
+    match value.as_deref() {
+        None | Some("") => "not set".to_string(),
+        Some(value) if value.starts_with(ENV_PREFIX) => value.to_string(),
+        Some(value) => {
+            let chars = value.chars().collect::<Vec<_>>();
+            let last = chars[chars.len().saturating_sub(4)..]
+                .iter()
+                .collect::<String>();
+            format!("****{}", last)
+        }
+    }
+}
+
+// the config without the secrets, and the secrets
+fn split_secrets(cfg: &Config) -> (Config, Secrets) {
+    let mut cfg = cfg.clone();
+    let mut secrets = Secrets::default();
+    if is_secret(&cfg.pri_key) {
+        secrets.pri_key = cfg.pri_key.take();
+    }
+    for (name, network) in cfg.networks.iter_mut() {
+        let mut network_secrets = NetworkSecrets::default();
+        if is_secret(&network.pri_key) {
+            network_secrets.pri_key = network.pri_key.take();
+        }
+        if is_secret(&network.explorer_api_key) {
+            network_secrets.explorer_api_key = network.explorer_api_key.take();
+        }
+        if network_secrets != NetworkSecrets::default() {
+            secrets.networks.insert(name.clone(), network_secrets);
+        }
+    }
+    (cfg, secrets)
+}
+
+// fill in the secrets not set in the config
+fn merge_secrets(cfg: &mut Config, secrets: Secrets) {
+    if cfg.pri_key.is_none() {
+        cfg.pri_key = secrets.pri_key;
+    }
+    for (name, network_secrets) in secrets.networks {
+        if let Some(network) = cfg.networks.get_mut(&name) {
+            if network.pri_key.is_none() {
+                network.pri_key = network_secrets.pri_key;
+            }
+            if network.explorer_api_key.is_none() {
+                network.explorer_api_key = network_secrets.explorer_api_key;
+            }
+        }
+    }
+}
+
+// the value of the env of an env:NAME secret, other secrets as they are
+pub fn resolve_secret(value: Option<String>) -> eyre::Result<Option<String>> {
+    match value
+        .as_deref()
+        .and_then(|value| value.strip_prefix(ENV_PREFIX))
+    {
+        Some(name) => env::var(name)
+            .map(Some)
+            .map_err(|_| eyre::eyre!("Env {} not set", name)),
+        None => Ok(value),
+    }
+}
+
+fn warn_secret(value: &Option<String>) {
+    if is_secret(value) {
+        println!(
+            "Key saved to {}, keep the .morge directory out of git, or give it as env:NAME to read it from the env",
+            INIT_SECRETS
+        );
+    }
+}
+
+// exclusive lock of the config, held from restoring it until the change is
+// saved so that concurrent morge processes don't overwrite each other's
+// changes, released when dropped
+pub struct ConfigLock {
+    _file: File,
+}
+
+thread_local! {
+    // whether this thread holds the config lock, which isn't reentrant
+    static LOCK_HELD: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn lock_cfg() -> eyre::Result<ConfigLock> {
+    let file = lock_exclusive(&project_file(INIT_LOCK))?;
+    LOCK_HELD.with(|held| held.set(true));
+    Ok(ConfigLock { _file: file })
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        LOCK_HELD.with(|held| held.set(false));
+    }
+}
+
+// the config lock, None if this thread already holds it
+fn lock_unless_held() -> eyre::Result<Option<ConfigLock>> {
+    match LOCK_HELD.with(Cell::get) {
+        true => Ok(None),
+        false => lock_cfg().map(Some),
+    }
+}
+
 pub fn restore_cfg() -> eyre::Result<Config> {
-    let file_str = fs::read_to_string(INIT_CFG)?;
-    let cfg = from_json(&file_str)?;
+    if let Some(cfg) = read_cfg()? {
+        return Ok(cfg);
+    }
+    // migrate under the config lock, from the config read again since another
+    // morge may have migrated or changed it meanwhile
+    let _lock = lock_unless_held()?;
+    match read_cfg()? {
+        Some(cfg) => Ok(cfg),
+        None => migrate_cfg(),
+    }
+}
+
+// the config, None if it must be migrated first
+fn read_cfg() -> eyre::Result<Option<Config>> {
+    if !config_file().exists() && project_file(LEGACY_CFG).exists() {
+        return Ok(None);
+    }
+    let (cfg, exposed) = load_cfg()?;
+    match cfg.version < CONFIG_VERSION || exposed {
+        true => Ok(None),
+        false => Ok(Some(prepare(cfg))),
+    }
+}
+
+// bring the config to the current version with its keys moved to the
+// secrets file and save it, with the config lock held
+fn migrate_cfg() -> eyre::Result<Config> {
+    let path = config_file();
+    if !path.exists() && project_file(LEGACY_CFG).exists() {
+        return migrate_legacy();
+    }
+    let (cfg, exposed) = load_cfg()?;
+    let cfg = migrate(cfg)?;
+    save(&cfg)?;
+    if exposed {
+        println!("Move the keys of {} to {}", path.display(), INIT_SECRETS);
+    }
+    Ok(prepare(cfg))
+}
+
+// the config merged with its secrets, and whether it holds keys itself
+fn load_cfg() -> eyre::Result<(Config, bool)> {
+    let path = config_file();
+    if !path.exists() {
+        return Err(eyre::eyre!(
+            "Config {} not found, please run morge init in the project root",
+            path.display()
+        ));
+    }
+    let file_str = fs::read_to_string(&path)?;
+    let mut cfg = from_toml(&file_str)?;
+    // keys written to morge.toml by hand or by an older morge
+    let exposed = split_secrets(&cfg).1 != Secrets::default();
+    let secrets_file = project_file(INIT_SECRETS);
+    if secrets_file.exists() {
+        let secrets = toml::from_str(&fs::read_to_string(secrets_file)?)?;
+        merge_secrets(&mut cfg, secrets);
+    }
+    if cfg.version > CONFIG_VERSION {
+        return Err(eyre::eyre!(
+            "{} is version {}, but this morge supports up to version {}, please upgrade morge",
+            path.display(),
+            cfg.version,
+            CONFIG_VERSION
+        ));
+    }
+    Ok((cfg, exposed))
+}
+
+fn prepare(mut cfg: Config) -> Config {
+    for contract in cfg.contracts.iter_mut() {
+        contract.compiler = cfg.compiler.clone();
+        if contract.artifact.is_none() {
+            contract.artifact = Some(contract.artifact_file());
+        }
+    }
+    cfg
+}
+
+// bring the config of an older version to the current one, step by step
+fn migrate(mut cfg: Config) -> eyre::Result<Config> {
+    // 0 to 1, the compiled abi and bytecode are no longer kept and the
+    // fields added since have defaults
+    if cfg.version == 0 {
+        cfg.version = 1;
+    }
+    // 1 to 2, contracts were added with absolute paths, the ones in the
+    // project are made relative to the root
+    if cfg.version == 1 {
+        let root = root()?;
+        for contract in cfg.contracts.iter_mut() {
+            if Path::new(&contract.contract).is_absolute() {
+                contract.contract = relative_path(&root, &contract.contract)?;
+            }
+        }
+        cfg.version = 2;
+    }
     Ok(cfg)
 }
 
+// move the json config to morge.toml, the json file is kept as a backup
+fn migrate_legacy() -> eyre::Result<Config> {
+    let legacy = project_file(LEGACY_CFG);
+    let json = fs::read_to_string(&legacy)?;
+    let cfg: Config = serde_json::from_str(&json)?;
+    save(&migrate(cfg)?)?;
+    let backup = project_file(&format!("{}.bak", LEGACY_CFG));
+    fs::rename(&legacy, &backup)?;
+    println!(
+        "Migrate {} to {} success, the old config is kept in {}",
+        LEGACY_CFG,
+        config_file().display(),
+        backup.display()
+    );
+    restore_cfg()
+}
+
 impl Config {
     pub fn new() -> Self {
-        Config {
-            rpc_url: None,
-            pri_key: None,
-            contracts: vec![],
-        }
+        Config::default()
     }
 
     pub fn set_rpc_and_key(&mut self, rpc_url: String, pri_key: String) -> eyre::Result<()> {
         self.rpc_url = Some(rpc_url);
         self.pri_key = Some(pri_key);
         save(self)?;
+        warn_secret(&self.pri_key);
         println!("Set rpc url and private key success");
         Ok(())
     }
 
+    // add or update the network profile, only the given fields are changed
+    pub fn set_network(&mut self, name: String, update: NetworkUpdate) -> eyre::Result<()> {
+        warn_secret(&update.pri_key);
+        warn_secret(&update.explorer_api_key);
+        self.networks
+            .entry(name.clone())
+            .or_default()
+            .update(update);
+        save(self)?;
+        println!("Set network {} success", name);
+        Ok(())
+    }
+
+    // add or replace the user-defined chain of the same name
+    pub fn set_chain(&mut self, chain: ChainInfo) -> eyre::Result<()> {
+        let name = chain.name.clone();
+        self.chains.retain(|item| item.name != chain.name);
+        self.chains.push(chain);
+        save(self)?;
+        println!("Set chain {} success", name);
+        Ok(())
+    }
+
+    // the built-in chains and the user-defined ones
+    pub fn chains(&self) -> ChainRegistry {
+        ChainRegistry::new(&self.chains)
+    }
+
+    // resolve the network profile to deploy, the settings of the default
+    // network and the top level rpc url and private key are used when no
+    // network is specified
+    pub fn network(&self, name: Option<&str>) -> eyre::Result<Network> {
+        let mut network = match name {
+            Some(name) => match self.networks.get(name) {
+                Some(network) => network.clone(),
+                // the local network deploys to anvil without being set
+                None if name == LOCAL_NETWORK => Network {
+                    local: Some(AnvilOptions::default()),
+                    ..Default::default()
+                },
+                None => return Err(eyre::eyre!("Network {} not found, please set", name)),
+            },
+            None => self
+                .networks
+                .get(DEFAULT_NETWORK)
+                .cloned()
+                .unwrap_or_default(),
+        };
+        if network.rpc_url.is_none() {
+            network.rpc_url = self.rpc_url.clone();
+        }
+        if network.pri_key.is_none() {
+            network.pri_key = self.pri_key.clone();
+        }
+        network.pri_key = resolve_secret(network.pri_key)?;
+        network.explorer_api_key = resolve_secret(network.explorer_api_key)?;
+        Ok(network)
+    }
+
     // add contract and args by specify -f x.sol:x --args a b c
     pub fn add_contract(&mut self, contract: String, args: Vec<String>) -> eyre::Result<()> {
         match is_contract_existed(contract.clone()) {
             true => {
                 let contract_info = ContractInfo::new(contract.clone(), args.clone());
-                if self
-                    .contracts
-                    .iter()
-                    .map(|contract| {
-                        contract.contract == contract_info.contract
-                            && contract.name == contract_info.name
-                    })
-                    .any(|x| x)
-                {
+                if self.contracts.iter().any(|contract| {
+                    contract.contract == contract_info.contract
+                        && contract.name == contract_info.name
+                }) {
                     println!("Contract {} already existed", contract);
                     return Ok(());
                 };
@@ -92,23 +587,22 @@ impl Config {
         match is_contract_existed(contract.clone()) {
             true => {
                 let contract_info = ContractInfo::new(contract.clone(), vec![]);
-                if !self
-                    .contracts
-                    .iter()
-                    .map(|contract| {
-                        contract.contract == contract_info.contract
-                            && contract.name == contract_info.name
-                    })
-                    .any(|x| x)
-                {
+                if !self.contracts.iter().any(|contract| {
+                    contract.contract == contract_info.contract
+                        && contract.name == contract_info.name
+                }) {
                     println!("Contract {} not exists", contract);
                     return Ok(());
                 };
 
-                self.contracts.retain(|item| {
-                    !(item.contract == contract_info.contract && item.name == contract_info.name)
+                let (removed, kept) = self.contracts.drain(..).partition(|item| {
+                    item.contract == contract_info.contract && item.name == contract_info.name
                 });
+                self.contracts = kept;
                 save(self)?;
+                for contract in removed.iter() {
+                    remove_artifact(&contract.artifact_file())?;
+                }
                 println!("Remove contract: {} success", contract);
                 Ok(())
             }
@@ -119,40 +613,64 @@ impl Config {
         }
     }
 
-    pub fn list() {
-        let cfg_path = Path::new(INIT_CFG);
-        if !cfg_path.exists() {
-            println!("Configuration file not existed");
-            return;
-        }
+    // print the config with its keys masked, the networks being listed
+    // even without the default rpc url and key
+    pub fn list() -> eyre::Result<()> {
+        let cfg = restore_cfg()?;
+        println!("Config version: {:?}", cfg.version);
+        println!("Compiler solc: {:?}", cfg.compiler.solc);
+        println!(
+            "Compiler optimizer runs: {:?} \n",
+            cfg.compiler.optimizer_runs
+        );
+        println!("Rpc url: {:?}", cfg.rpc_url);
+        println!("Private key: {} \n", mask_secret(&cfg.pri_key));
 
-        let cfg = restore_cfg().unwrap();
-        if cfg.rpc_url.is_none() {
-            println!("Rpc url not set, please set");
-            return;
+        for (name, network) in cfg.networks.iter() {
+            println!("Network name: {:?}", name);
+            println!("Network rpc url: {:?}", network.rpc_url);
+            println!("Network private key: {}", mask_secret(&network.pri_key));
+            println!("Network fallback rpc urls: {:?}", network.fallback_rpc_urls);
+            println!("Network chain id: {:?}", network.chain_id);
+            println!("Network confirmations: {:?}", network.confirmations);
+            println!("Network timeout: {:?}", network.timeout);
+            println!("Network max retries: {:?}", network.max_retries);
+            println!("Network quorum: {:?}", network.quorum);
+            println!("Network local anvil: {:?}", network.local);
+            println!("Network explorer url: {:?}", network.explorer_url);
+            println!(
+                "Network explorer api key: {} \n",
+                mask_secret(&network.explorer_api_key)
+            );
         }
 
-        if cfg.pri_key.is_none() {
-            println!("Private key not set, please set");
-            return;
+        for chain in cfg.chains.iter() {
+            println!("Chain name: {:?}", chain.name);
+            println!("Chain id: {:?}", chain.chain_id);
+            println!("Chain symbol: {:?}", chain.symbol);
+            println!("Chain explorer url: {:?}", chain.explorer_url);
+            println!("Chain api key env: {:?}", chain.api_key_env);
+            println!("Chain eip1559: {:?}", chain.eip1559);
+            println!("Chain confirmations: {:?} \n", chain.confirmations);
         }
 
-        let contracts = cfg.contracts;
-        if contracts.is_empty() {
+        if cfg.contracts.is_empty() {
             println!("Have no any contract file to deploy.");
-            return;
         }
-
-        for contract_info in contracts.iter() {
+        for contract_info in cfg.contracts.iter() {
             println!("Contract name: {:?}", contract_info.name);
             println!("Contract contract: {:?}", contract_info.contract);
             println!("Contract args: {:?} \n", contract_info.args);
         }
+        Ok(())
     }
 
     pub fn clean(&mut self) -> eyre::Result<()> {
         self.rpc_url = None;
         self.pri_key = None;
+        self.compiler = CompilerSettings::default();
+        self.networks = BTreeMap::new();
+        self.chains = vec![];
         self.contracts = vec![];
         save(self)?;
         Ok(())
@@ -163,9 +681,13 @@ impl Config {
 mod tests {
 
     use super::*;
+    use crate::project::TempRoot;
 
-    fn setup() {
+    // a config with the rpc url and the private key in a project root of the
+    // test, removed when the root is dropped
+    fn setup() -> TempRoot {
         // given
+        let root = TempRoot::new();
         let mut cfg = Config::new();
         let rpc_url = "http://localhost:8545";
         let pri_key = "0x1234567890123456789012345678901234567890123456789012345678901234";
@@ -175,11 +697,12 @@ mod tests {
         save(&cfg).unwrap();
 
         // then
-        assert!(Path::new(INIT_CFG).exists());
+        assert!(config_file().exists());
         let cfg = restore_cfg().unwrap();
         assert_eq!(cfg.rpc_url, Some(rpc_url.into()));
         assert_eq!(cfg.pri_key, Some(pri_key.into()));
         assert_eq!(cfg.contracts, vec![]);
+        root
     }
 
     fn teardown() {
@@ -191,14 +714,243 @@ mod tests {
 
     #[test]
     fn test_init() {
-        setup();
+        let _root = setup();
+        teardown();
+    }
+
+    #[test]
+    fn test_set_network() {
+        // given
+        let _root = setup();
+        let mut cfg = restore_cfg().unwrap();
+
+        // when
+        let update = NetworkUpdate {
+            confirmations: Some(5),
+            timeout: Some(60),
+            ..Default::default()
+        };
+        cfg.set_network("polygon".into(), update).unwrap();
+        let update = NetworkUpdate {
+            rpc_url: Some("http://localhost:8546".into()),
+            fallback_rpc_urls: Some(vec!["http://localhost:8547".into()]),
+            max_retries: Some(10),
+            quorum: Some(2),
+            chain_id: Some(56),
+            explorer_url: Some("http://localhost:4000/api".into()),
+            ..Default::default()
+        };
+        cfg.set_network("bsc".into(), update).unwrap();
+
+        // then
+        let cfg = restore_cfg().unwrap();
+        let polygon = cfg.network(Some("polygon")).unwrap();
+        assert_eq!(polygon.rpc_url, cfg.rpc_url);
+        assert_eq!(polygon.confirmations, Some(5));
+        assert_eq!(polygon.timeout, 60);
+        let bsc = cfg.network(Some("bsc")).unwrap();
+        assert_eq!(bsc.rpc_url, Some("http://localhost:8546".into()));
+        assert_eq!(bsc.pri_key, cfg.pri_key);
+        assert_eq!(bsc.confirmations, None);
+        assert_eq!(bsc.confirmations_on(None), DEFAULT_CONFIRMATIONS);
+        assert_eq!(bsc.max_retries, 10);
+        assert_eq!(bsc.quorum, 2);
+        assert!(bsc.check_chain_id(56).is_ok());
+        assert!(bsc.check_chain_id(137).is_err());
+        assert!(polygon.check_chain_id(56).is_ok());
+        assert_eq!(bsc.explorer_url, Some("http://localhost:4000/api".into()));
+        assert!(bsc.explorer_api_key.is_none());
+        assert_eq!(
+            bsc.rpc_urls(),
+            vec!["http://localhost:8546", "http://localhost:8547"]
+        );
+        assert_eq!(polygon.max_retries, DEFAULT_MAX_RETRIES);
+        assert!(cfg.network(Some("eth")).is_err());
+        assert_eq!(
+            cfg.network(Some(LOCAL_NETWORK)).unwrap().local,
+            Some(AnvilOptions::default())
+        );
+        assert!(polygon.local.is_none());
+        assert_eq!(cfg.network(None).unwrap().rpc_url, cfg.rpc_url);
+        teardown();
+    }
+
+    #[test]
+    fn test_secrets_kept_out_of_config() {
+        // given
+        let _root = setup();
+        let mut cfg = restore_cfg().unwrap();
+        let pri_key = "0x1234567890123456789012345678901234567890123456789012345678901234";
+        env::set_var("MORGE_TEST_POLYGON_KEY", pri_key);
+
+        // when
+        cfg.set_rpc_and_key("http://localhost:8545".into(), pri_key.into())
+            .unwrap();
+        let update = NetworkUpdate {
+            pri_key: Some("env:MORGE_TEST_POLYGON_KEY".into()),
+            explorer_api_key: Some("APIKEY".into()),
+            ..Default::default()
+        };
+        cfg.set_network("polygon".into(), update).unwrap();
+
+        // then
+        let toml = fs::read_to_string(config_file()).unwrap();
+        assert!(!toml.contains(pri_key));
+        assert!(!toml.contains("APIKEY"));
+        assert!(toml.contains("env:MORGE_TEST_POLYGON_KEY"));
+        assert!(fs::read_to_string(project_file(INIT_SECRETS))
+            .unwrap()
+            .contains(pri_key));
+        let cfg = restore_cfg().unwrap();
+        assert_eq!(cfg.pri_key, Some(pri_key.to_string()));
+        let polygon = cfg.network(Some("polygon")).unwrap();
+        assert_eq!(polygon.pri_key, Some(pri_key.to_string()));
+        assert_eq!(polygon.explorer_api_key, Some("APIKEY".to_string()));
+        env::remove_var("MORGE_TEST_POLYGON_KEY");
+        assert!(cfg.network(Some("polygon")).is_err());
+        teardown();
+    }
+
+    #[test]
+    fn test_migrate_legacy_config() {
+        // given
+        let root = setup();
+        fs::remove_file(config_file()).unwrap();
+        // contracts were added with absolute paths
+        let contract = root.path.join("contracts/contract.sol");
+        let legacy = r#"{
+            "rpc_url": "http://localhost:8545",
+            "pri_key": "0x1234",
+            "networks": { "polygon": { "rpc_url": null, "pri_key": null, "confirmations": 5 } },
+            "contracts": [{
+                "name": "SimpleStorage",
+                "contract": "CONTRACT",
+                "args": ["value"],
+                "abi": [],
+                "bytecode": "0x6080"
+            }, {
+                "name": "Outside",
+                "contract": "/outside/contract.sol",
+                "args": []
+            }]
+        }"#
+        .replace("CONTRACT", &contract.to_string_lossy());
+        fs::write(project_file(LEGACY_CFG), legacy).unwrap();
+
+        // when
+        let cfg = restore_cfg().unwrap();
+
+        // then
+        assert_eq!(cfg.version, CONFIG_VERSION);
+        assert_eq!(cfg.rpc_url, Some("http://localhost:8545".into()));
+        assert_eq!(cfg.networks["polygon"].confirmations, Some(5));
+        assert_eq!(cfg.contracts[0].args, vec!["value".to_string()]);
+        assert_eq!(cfg.contracts[0].contract, "contracts/contract.sol");
+        assert_eq!(cfg.contracts[1].contract, "/outside/contract.sol");
+        assert!(config_file().exists());
+        assert!(!project_file(LEGACY_CFG).exists());
+        assert!(project_file(&format!("{}.bak", LEGACY_CFG)).exists());
+        let toml = fs::read_to_string(config_file()).unwrap();
+        assert!(toml.contains("version = 2"));
+        assert!(!toml.contains("bytecode"));
+
+        // a config of a newer morge is refused
+        save(&Config {
+            version: CONFIG_VERSION + 1,
+            ..cfg
+        })
+        .unwrap();
+        assert!(restore_cfg().is_err());
+        save(&Config::new()).unwrap();
+        teardown();
+    }
+
+    #[test]
+    fn test_migrate_under_lock() {
+        // given
+        let _root = setup();
+        fs::write(
+            config_file(),
+            "version = 1\nrpc_url = \"http://localhost:8545\"\npri_key = \"0x1234\"\n",
+        )
+        .unwrap();
+
+        // when migrated by a command already holding the lock
+        let lock = lock_cfg().unwrap();
+        let cfg = restore_cfg().unwrap();
+        drop(lock);
+
+        // then
+        assert_eq!(cfg.version, CONFIG_VERSION);
+        assert_eq!(cfg.pri_key, Some("0x1234".into()));
+        let toml = fs::read_to_string(config_file()).unwrap();
+        assert!(!toml.contains("0x1234"));
+
+        // and by a read only command, which takes the lock itself
+        fs::write(config_file(), "version = 1\n").unwrap();
+        assert_eq!(restore_cfg().unwrap().version, CONFIG_VERSION);
+        assert!(lock_unless_held().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_list() {
+        // given a config with a network profile only
+        let _root = setup();
+        let mut cfg = Config::new();
+        let update = NetworkUpdate {
+            rpc_url: Some("http://localhost:8545".into()),
+            pri_key: Some("0xac0974bec39a17e36ba4a6b4d238ff944bacb478".into()),
+            ..Default::default()
+        };
+        cfg.set_network("polygon".into(), update).unwrap();
+
+        // then
+        assert!(Config::list().is_ok());
+        assert_eq!(mask_secret(&cfg.networks["polygon"].pri_key), "****b478");
+        assert_eq!(mask_secret(&Some("env:PRI_KEY".into())), "env:PRI_KEY");
+        assert_eq!(mask_secret(&None), "not set");
+
+        // a missing config is an error
+        fs::remove_file(config_file()).unwrap();
+        assert!(Config::list().is_err());
+    }
+
+    #[test]
+    fn test_set_chain() {
+        // given
+        let _root = setup();
+        let mut cfg = restore_cfg().unwrap();
+        let base = ChainInfo {
+            confirmations: 3,
+            ..ChainInfo::new("base", 8453)
+        };
+
+        // when
+        cfg.set_chain(ChainInfo::new("base", 84531)).unwrap();
+        cfg.set_chain(base.clone()).unwrap();
+        cfg.set_network("base".into(), NetworkUpdate::default())
+            .unwrap();
+
+        // then
+        let cfg = restore_cfg().unwrap();
+        assert_eq!(cfg.chains, vec![base.clone()]);
+        let chains = cfg.chains();
+        assert_eq!(chains.by_chain_id(8453), Some(&base));
+        assert!(chains.by_name("polygon").is_some());
+        let network = cfg.network(Some("base")).unwrap();
+        assert_eq!(network.confirmations_on(chains.by_chain_id(8453)), 3);
+        let network = Network {
+            confirmations: Some(5),
+            ..network
+        };
+        assert_eq!(network.confirmations_on(chains.by_chain_id(8453)), 5);
         teardown();
     }
 
     #[test]
     fn test_add_contract_success() {
         // given
-        setup();
+        let _root = setup();
         let mut cfg = restore_cfg().unwrap();
         let contract_file = Path::new(&env!("CARGO_MANIFEST_DIR"))
             .join("examples/contract.sol:SimpleStorage")
@@ -227,7 +979,7 @@ mod tests {
     #[should_panic]
     fn test_add_contract_failed_with_wrong_path() {
         // given
-        test_init();
+        let _root = setup();
         let mut cfg = restore_cfg().unwrap();
         let contract_file = Path::new(&env!("CARGO_MANIFEST_DIR"))
             .join("examples/contract.sol")
@@ -244,7 +996,7 @@ mod tests {
     #[test]
     fn test_remove_contract_success() {
         // given
-        setup();
+        let _root = setup();
         let mut cfg = restore_cfg().unwrap();
 
         let contract_01 = Path::new(&env!("CARGO_MANIFEST_DIR"))
@@ -282,7 +1034,7 @@ mod tests {
     #[should_panic]
     fn test_remove_contract_failed_with_wrong_path() {
         // given
-        setup();
+        let _root = setup();
         let mut cfg = restore_cfg().unwrap();
         let contract_file = Path::new(&env!("CARGO_MANIFEST_DIR"))
             .join("examples/contract.sol:SimpleStorage")
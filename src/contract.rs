@@ -1,21 +1,81 @@
 use core::panic;
-use ethers::{abi::Token, core::abi::Contract as Abi, prelude::*};
-use eyre::Result;
+use ethers::solc::{CompilerInput, CompilerOutput};
+use ethers::{abi::Function, core::abi::Contract as Abi, prelude::*};
+use eyre::WrapErr;
 use serde::{Deserialize, Serialize};
-use std::io;
 use std::path::Path;
-use std::sync::Arc;
 
+use crate::artifact::{artifact_path, restore_artifact, Artifact};
+use crate::nonce::NonceManager;
 use crate::utils::{fs::*, parse::*};
 
-// contract info to deploy
+// contract info to deploy, only the contract file, name and args are kept in
+// the config, the rest is generated by the compilation and saved as its artifact
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ContractInfo {
     pub name: String,
     pub contract: String,
+    #[serde(default)]
     pub args: Vec<String>,
+    // calls made on the contract once it is deployed, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<ContractCall>,
+    // artifact file of the compiled contract, relative to the project root
+    #[serde(default)]
+    pub artifact: Option<String>,
+    #[serde(skip)]
     pub abi: Abi,
+    #[serde(skip)]
     pub bytecode: Bytes,
+    #[serde(skip)]
+    pub runtime_bytecode: Bytes,
+    // start and length of the immutables in the runtime bytecode
+    #[serde(skip)]
+    pub immutables: Vec<(usize, usize)>,
+    // the compiler settings of the project
+    #[serde(skip)]
+    pub compiler: CompilerSettings,
+}
+
+// a call of a function of the deployed contract, by its name or its
+// signature as transferOwnership(address) when overloaded, the args are given
+// as the constructor args
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContractCall {
+    pub function: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// solc and optimizer settings of the project, the solc on the PATH without
+// the optimizer if not set
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompilerSettings {
+    // path of the solc binary
+    #[serde(default)]
+    pub solc: Option<String>,
+    // runs of the optimizer, which is disabled if not set
+    #[serde(default)]
+    pub optimizer_runs: Option<usize>,
+}
+
+impl CompilerSettings {
+    pub fn solc(&self) -> Solc {
+        match &self.solc {
+            Some(solc) => Solc::new(solc),
+            None => Solc::default(),
+        }
+    }
+
+    pub fn compile(&self, path: &str) -> eyre::Result<CompilerOutput> {
+        let mut input = CompilerInput::new(path)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre::eyre!("No source to compile in {}", path))?;
+        input.settings.optimizer.enabled = Some(self.optimizer_runs.is_some());
+        input.settings.optimizer.runs = self.optimizer_runs;
+        Ok(self.solc().compile(&input)?)
+    }
 }
 
 impl ContractInfo {
@@ -37,78 +97,256 @@ impl ContractInfo {
             name: contract_vec[1].to_string(),
             contract: sol_file.to_string(),
             args,
+            calls: vec![],
+            artifact: Some(artifact_path(sol_file, contract_vec[1])),
             abi: Abi::default(),
             bytecode: Bytes::default(),
+            runtime_bytecode: Bytes::default(),
+            immutables: vec![],
+            compiler: CompilerSettings::default(),
         }
     }
 
-    pub async fn compile(&mut self) -> Result<(), io::Error> {
-        match is_contract_existed(self.contract.clone()) && self.contract.ends_with(".sol") {
-            true => {
-                let compiled = Solc::default()
-                    .compile_source(self.contract.clone())
-                    .expect("Could not compile contracts");
-                let (abi, bytecode, _runtime_bytecode) = compiled
-                    .find(self.name.to_string())
-                    .expect("could not find contract name")
-                    .into_parts_or_default();
-                self.abi = abi;
-                self.bytecode = bytecode;
-                Ok(())
+    pub async fn compile(&mut self) -> eyre::Result<()> {
+        if !is_contract_existed(self.contract.clone()) || !self.contract.ends_with(".sol") {
+            return Err(eyre::eyre!(
+                "Contract {} not found or isn't a sol file",
+                self.contract
+            ));
+        }
+        let compiled = self
+            .compiler
+            .compile(&self.contract)
+            .wrap_err_with(|| format!("Failed to compile {}", self.contract))?;
+        if compiled.has_error() {
+            let errors = compiled
+                .errors
+                .iter()
+                .filter(|error| error.severity.is_error())
+                .map(|error| error.message.clone())
+                .collect::<Vec<_>>();
+            return Err(eyre::eyre!(
+                "Failed to compile {}:\n  {}",
+                self.contract,
+                errors.join("\n  ")
+            ));
+        }
+        let (abi, bytecode, runtime_bytecode) = match compiled.find(&self.name) {
+            Some(found) => found.into_parts_or_default(),
+            None => {
+                let names = compiled
+                    .contracts
+                    .values()
+                    .flat_map(|contracts| contracts.keys().cloned())
+                    .collect::<Vec<_>>();
+                return Err(eyre::eyre!(
+                    "Contract {} not found in {}, available contracts: {}",
+                    self.name,
+                    self.contract,
+                    names.join(", ")
+                ));
             }
-            false => {
-                println!("Contract {} not found", self.contract);
-                Ok(())
+        };
+        self.abi = abi;
+        self.bytecode = bytecode;
+        self.runtime_bytecode = runtime_bytecode;
+        self.immutables = immutable_references(&compiled, &self.name);
+        Artifact::of(self).save(&self.artifact_file())
+    }
+
+    pub fn artifact_file(&self) -> String {
+        self.artifact
+            .clone()
+            .unwrap_or_else(|| artifact_path(&self.contract, &self.name))
+    }
+
+    // take the abi and bytecode of the last compilation from the artifact,
+    // compile if there is none or it was compiled with other settings
+    pub async fn load(&mut self) -> eyre::Result<()> {
+        match restore_artifact(&self.artifact_file())? {
+            Some(artifact) if artifact.compiler == self.compiler => {
+                self.abi = artifact.abi;
+                self.bytecode = artifact.bytecode;
+                self.runtime_bytecode = artifact.runtime_bytecode;
+                self.immutables = artifact.immutables;
             }
+            _ => self.compile().await?,
         }
+        Ok(())
     }
 
-    pub async fn run<M: Middleware + 'static>(&mut self, provider: M) -> eyre::Result<()> {
-        // compile to get abi and bytecode
-        self.compile().await?;
-        let abi = self.abi.clone();
-        let bin = self.bytecode.clone();
-
-        // Add arguments to constructor
-        let args = parse_constructor_args(&abi.clone().constructor.unwrap(), &self.args)?;
+    pub async fn run<M: Middleware + 'static>(
+        &mut self,
+        manager: &mut NonceManager<M>,
+    ) -> eyre::Result<TransactionReceipt> {
+        // compile and add arguments to constructor
+        let data = self.deployment_data().await?;
 
         // deploy contract
-        self.deploy(abi.clone(), bin.clone(), args, provider)
-            .await?;
-        Ok(())
+        self.deploy(data, manager).await
+    }
+
+    // compile and encode constructor args, return the contract creation code
+    pub async fn deployment_data(&mut self) -> eyre::Result<Bytes> {
+        self.compile().await?;
+        let args = parse_constructor_args(self.abi.constructor.as_ref(), &self.args)
+            .wrap_err_with(|| format!("Failed to deploy {}:{}", self.contract, self.name))?;
+        let data = match &self.abi.constructor {
+            Some(constructor) => constructor.encode_input(self.bytecode.to_vec(), &args)?,
+            None => self.bytecode.to_vec(),
+        };
+        Ok(data.into())
+    }
+
+    // abi encode the constructor args with the abi of the last compilation
+    pub async fn constructor_args(&mut self) -> eyre::Result<Bytes> {
+        self.load().await?;
+        let args = parse_constructor_args(self.abi.constructor.as_ref(), &self.args)?;
+        Ok(ethers::abi::encode(&args).into())
     }
 
     pub async fn deploy<M: Middleware + 'static>(
         &self,
-        abi: Abi,
-        bin: Bytes,
-        args: Vec<Token>,
-        provider: M,
-    ) -> eyre::Result<()> {
-        let provider = Arc::new(provider);
-        let factory = ContractFactory::new(abi, bin, provider.clone());
-
+        data: Bytes,
+        manager: &mut NonceManager<M>,
+    ) -> eyre::Result<TransactionReceipt> {
         // start deploy
-        let deployer = factory.deploy_tokens(args.clone())?.legacy();
-        let deployer_address = provider
-            .default_sender()
-            .expect("no sender address set for provider");
-        println!("Deployer address: {}", deployer_address);
-        let (deployed_contract, receipt) = deployer.send_with_receipt().await?;
-        let address = deployed_contract.address();
-
-        println!("Deployer: {deployer_address:?}");
-        println!("Deployed to: {:?}", address);
-        println!("Transaction hash: {:?}\n", receipt.transaction_hash);
+        let tx = TransactionRequest::new().data(data);
+        println!("Deployer address: {:?}", manager.address());
+        let receipt = manager.send(tx).await?;
+        let address = check_deployed(manager.client(), &receipt)
+            .await
+            .wrap_err_with(|| format!("Failed to deploy {}:{}", self.contract, self.name))?;
+        report_receipt(&receipt);
 
-        Ok(())
+        for call in self.calls.iter() {
+            let tx = TransactionRequest::new()
+                .to(address)
+                .data(self.calldata(call)?);
+            let call_receipt = manager.send(tx).await.wrap_err_with(|| {
+                format!(
+                    "Failed to call {} of {}:{}",
+                    call.function, self.contract, self.name
+                )
+            })?;
+            println!(
+                "Call {} of {}:{}, transaction hash: {:?}",
+                call.function, self.contract, self.name, call_receipt.transaction_hash
+            );
+        }
+        Ok(receipt)
+    }
+
+    // abi encode the call with the abi of the last compilation
+    pub fn calldata(&self, call: &ContractCall) -> eyre::Result<Bytes> {
+        let function = self
+            .abi
+            .functions()
+            .find(|function| {
+                function.name == call.function || function_signature(function) == call.function
+            })
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "Function {} not found in {}:{}",
+                    call.function,
+                    self.contract,
+                    self.name
+                )
+            })?;
+        if !call.function.contains('(') && self.abi.functions_by_name(&function.name)?.len() > 1 {
+            return Err(eyre::eyre!(
+                "Function {} of {}:{} is overloaded, call it by its signature as {}",
+                call.function,
+                self.contract,
+                self.name,
+                function_signature(function)
+            ));
+        }
+        let args = parse_function_args(function, &call.args)?;
+        Ok(function.encode_input(&args)?.into())
+    }
+}
+
+// the signature of the function without the returns, as setValue(string)
+fn function_signature(function: &Function) -> String {
+    let inputs = function
+        .inputs
+        .iter()
+        .map(|input| input.kind.to_string())
+        .collect::<Vec<_>>();
+    format!("{}({})", function.name, inputs.join(","))
+}
+
+// start and length of every reference to the immutables of the contract in
+// its runtime bytecode, solc leaves them zero and they are filled in at deployment
+fn immutable_references(output: &CompilerOutput, name: &str) -> Vec<(usize, usize)> {
+    output
+        .contracts
+        .values()
+        .find_map(|contracts| contracts.get(name))
+        .and_then(|contract| contract.evm.as_ref())
+        .and_then(|evm| evm.deployed_bytecode.as_ref())
+        .map(|code| {
+            code.immutable_references
+                .values()
+                .flatten()
+                .map(|offsets| (offsets.start as usize, offsets.length as usize))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// a mined contract creation only deployed the contract if it succeeded and
+// left code at the contract address
+pub async fn check_deployed<M: Middleware>(
+    client: &M,
+    receipt: &TransactionReceipt,
+) -> eyre::Result<Address> {
+    if receipt.status != Some(1.into()) {
+        return Err(eyre::eyre!(
+            "Contract creation {:?} reverted in block {}",
+            receipt.transaction_hash,
+            receipt.block_number.unwrap_or_default()
+        ));
+    }
+    let address = receipt.contract_address.ok_or_else(|| {
+        eyre::eyre!(
+            "Transaction {:?} is not a contract creation",
+            receipt.transaction_hash
+        )
+    })?;
+    let code = client
+        .get_code(address, receipt.block_number.map(|block| block.into()))
+        .await
+        .map_err(|err| eyre::eyre!("Failed to get the code of {:?}: {}", address, err))?;
+    if code.as_ref().is_empty() {
+        return Err(eyre::eyre!(
+            "Contract creation {:?} left no code at {:?}",
+            receipt.transaction_hash,
+            address
+        ));
     }
+    Ok(address)
+}
+
+// print the deployment result of a mined contract creation transaction
+pub fn report_receipt(receipt: &TransactionReceipt) {
+    println!("Deployer: {:?}", receipt.from);
+    println!(
+        "Deployed to: {:?}",
+        receipt.contract_address.unwrap_or_default()
+    );
+    println!("Transaction hash: {:?}\n", receipt.transaction_hash);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ethers::utils::Anvil;
+    use crate::config::Network;
+    use crate::nonce::ReplaceOptions;
+    use crate::project::TempRoot;
+    use ethers::abi::{Constructor, Param, ParamType};
+    use ethers::utils::{id, Anvil};
     use std::{
         future::Future,
         time::{Duration, SystemTime},
@@ -125,12 +363,18 @@ mod tests {
     #[tokio::test]
     async fn test_deploy_success() {
         // given
+        let _root = TempRoot::new();
         let mut contract_info = ContractInfo {
             name: "SimpleStorage".to_string(),
             contract: "examples/contract.sol".to_string(),
             args: vec!["value".into()],
+            calls: vec![],
+            artifact: None,
             abi: Abi::default(),
             bytecode: Bytes::default(),
+            runtime_bytecode: Bytes::default(),
+            immutables: vec![],
+            compiler: CompilerSettings::default(),
         };
 
         // use dev env here
@@ -139,12 +383,166 @@ mod tests {
         // need declare here to guarantee anvil's lifetime
         let anvil = &Anvil::new().spawn();
         // use anvil endpoint here
-        let client = get_provider(&anvil, "".to_string(), "".to_string()).await;
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
 
         // when
         run_at_least_duration(Duration::from_millis(250), async {
-            contract_info.run(client).await.unwrap();
+            contract_info.run(&mut manager).await.unwrap();
         })
         .await
     }
+
+    #[tokio::test]
+    async fn test_deploy_reverted() {
+        // given
+        let _root = TempRoot::new();
+        let mut reverts = ContractInfo::new("examples/reverts.sol:Reverts".into(), vec![]);
+        let anvil = &Anvil::new().spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+
+        // when
+        let reverted = reverts.run(&mut manager).await;
+        // creation code of a single STOP, which succeeds but leaves no code
+        let tx = TransactionRequest::new().data(vec![0u8]).gas(100000u64);
+        let receipt = manager.send(tx).await.unwrap();
+        let empty = check_deployed(manager.client(), &receipt).await;
+
+        // then
+        assert!(reverted.is_err());
+        assert!(empty.unwrap_err().to_string().contains("left no code"));
+    }
+
+    #[tokio::test]
+    async fn test_deploy_without_constructor() {
+        // given
+        let _root = TempRoot::new();
+        let mut counter = ContractInfo::new("examples/counter.sol:Counter".into(), vec![]);
+        let anvil = &Anvil::new().spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+
+        // when
+        let receipt = counter.run(&mut manager).await.unwrap();
+
+        // then
+        assert!(receipt.contract_address.is_some());
+        counter.args = vec!["1".into()];
+        let err = counter.deployment_data().await.unwrap_err();
+        assert!(format!("{:?}", err).contains("expected no constructor args"));
+    }
+
+    #[tokio::test]
+    async fn test_deploy_with_calls() {
+        // given
+        let _root = TempRoot::new();
+        let mut contract = ContractInfo::new(
+            "examples/contract.sol:SimpleStorage".into(),
+            vec!["a".into()],
+        );
+        contract.calls = vec![ContractCall {
+            function: "setValue".into(),
+            args: vec!["b".into()],
+        }];
+        let anvil = &Anvil::new().spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+
+        // when
+        contract.run(&mut manager).await.unwrap();
+
+        // then the call is sent after the creation
+        assert_eq!(manager.next_nonce().await.unwrap(), 2.into());
+    }
+
+    #[test]
+    fn test_calldata() {
+        let mut contract = ContractInfo::new("examples/token.sol:Token".into(), vec![]);
+        contract.abi = ethers::abi::parse_abi(&[
+            "function transferOwnership(address owner)",
+            "function mint(uint256 amount)",
+            "function mint(address to, uint256 amount)",
+        ])
+        .unwrap();
+        let call = |function: &str, args: &[&str]| ContractCall {
+            function: function.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        };
+        let owner = "0x0000000000000000000000000000000000000001";
+
+        let data = contract
+            .calldata(&call("transferOwnership", &[owner]))
+            .unwrap();
+        assert_eq!(data.as_ref()[..4], id("transferOwnership(address)"));
+        let data = contract
+            .calldata(&call("mint(address,uint256)", &[owner, "1 ether"]))
+            .unwrap();
+        assert_eq!(data.as_ref()[..4], id("mint(address,uint256)"));
+
+        let overloaded = contract.calldata(&call("mint", &["1"])).unwrap_err();
+        assert!(overloaded.to_string().contains("as mint(uint256)"));
+        let missing = contract.calldata(&call("burn", &[])).unwrap_err();
+        assert!(missing.to_string().contains("Function burn not found"));
+        let invalid = contract
+            .calldata(&call("transferOwnership", &[]))
+            .unwrap_err();
+        assert!(invalid
+            .to_string()
+            .contains("expected 1 args transferOwnership(address owner), got 0"));
+    }
+
+    #[tokio::test]
+    async fn test_compile_errors() {
+        // given
+        let _root = TempRoot::new();
+        let mut typo = ContractInfo::new("examples/contract.sol:SimpleStorag".into(), vec![]);
+        let mut missing = ContractInfo::new("examples/missing.sol:SimpleStorage".into(), vec![]);
+
+        // when
+        let typo = typo.compile().await.unwrap_err();
+        let missing = missing.compile().await.unwrap_err();
+
+        // then the available contracts are listed
+        assert!(typo
+            .to_string()
+            .contains("Contract SimpleStorag not found in examples/contract.sol, available contracts: SimpleStorage"));
+        assert!(missing.to_string().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_constructor_args_checked() {
+        // given
+        let _root = TempRoot::new();
+        let mut contract = ContractInfo::new("examples/contract.sol:SimpleStorage".into(), vec![]);
+
+        // when
+        let missing = contract.deployment_data().await.unwrap_err();
+        contract.args = vec!["a".into(), "b".into()];
+        let extra = contract.deployment_data().await.unwrap_err();
+
+        // then
+        assert!(format!("{:?}", missing)
+            .contains("expected 1 constructor args constructor(string value), got 0"));
+        assert!(format!("{:?}", extra).contains("got 2"));
+        let constructor = Constructor {
+            inputs: vec![Param {
+                name: "owner".into(),
+                kind: ParamType::Address,
+                internal_type: None,
+            }],
+        };
+        assert_eq!(
+            check_constructor_args(Some(&constructor), &["0x1234".into()]),
+            vec!["arg 0 \"0x1234\" is not a valid address for owner, expected a 0x prefixed address of 40 hex digits".to_string()]
+        );
+    }
 }
@@ -1,23 +1,65 @@
-use crate::config::{restore_cfg, save, Config};
+use crate::chain::{amount, ChainInfo, ChainRegistry};
+use crate::check::bytecode_matches;
+use crate::config::{lock_cfg, restore_cfg, save, Config, Network, NetworkUpdate, DEFAULT_NETWORK};
+use crate::contract::{check_deployed, report_receipt, ContractInfo};
+use crate::explorer::{Explorer, VerifyOptions, VerifyRequest, VerifyStatus};
+use crate::journal::{restore_journal, Deployment, Journal};
+use crate::local::{target_node, AnvilOptions, Node, LOCAL_NETWORK};
+use crate::nonce::{NonceManager, ReplaceOptions};
+use crate::offline::SignedBatch;
+use crate::project::project_file;
+use crate::safe::SafeBatch;
 use crate::utils::parse::*;
-use crate::verify::Verify;
-use ethers::utils::Anvil;
+use crate::validate::validate;
+use crate::verify::{report_tx, RecordStatus, Verify};
+use crate::INIT_NODE_STATE;
+use ethers::prelude::*;
+use eyre::WrapErr;
+use futures::future;
+use futures::stream::{self, StreamExt};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 
 pub struct Executer {
     pub cfg: Config,
+    pub replace: ReplaceOptions,
+    pub network: Option<String>,
+    // verify the source of every deployed contract when set
+    pub verify: Option<VerifyOptions>,
+}
+
+impl Default for Executer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Executer {
     pub fn new() -> Self {
         Self {
-            cfg: Config {
-                rpc_url: None,
-                pri_key: None,
-                contracts: vec![],
-            },
+            cfg: Config::new(),
+            replace: ReplaceOptions::default(),
+            network: None,
+            verify: None,
         }
     }
 
+    pub fn with_network(mut self, network: Option<String>) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn with_verify(mut self, verify: Option<VerifyOptions>) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn with_replace_options(mut self, replace: ReplaceOptions) -> Self {
+        self.replace = replace;
+        self
+    }
+
     pub fn init() -> eyre::Result<()> {
         save(&Config::new())?;
         println!("Init config file success");
@@ -25,6 +67,7 @@ impl Executer {
     }
 
     pub fn set_rpc_and_key(rpc_url: &str, pri_key: &str) -> eyre::Result<()> {
+        let _lock = lock_cfg()?;
         let mut cfg = restore_cfg()?;
         cfg.set_rpc_and_key(rpc_url.to_string(), pri_key.to_string())?;
         Ok(())
@@ -34,49 +77,122 @@ impl Executer {
         self.cfg = cfg;
     }
 
-    pub fn add_contract(contract: &str, args: Vec<String>) -> eyre::Result<()> {
+    pub fn set_network(name: &str, update: NetworkUpdate) -> eyre::Result<()> {
+        let _lock = lock_cfg()?;
+        let mut cfg = restore_cfg()?;
+        cfg.set_network(name.to_string(), update)?;
+        Ok(())
+    }
+
+    pub fn set_chain(chain: ChainInfo) -> eyre::Result<()> {
+        let _lock = lock_cfg()?;
+        let mut cfg = restore_cfg()?;
+        cfg.set_chain(chain)?;
+        Ok(())
+    }
+
+    // the args are given from dir, their @file paths are kept relative to the
+    // project root as the contract path
+    pub fn add_contract(dir: &Path, contract: &str, args: Vec<String>) -> eyre::Result<()> {
+        let args = relative_file_args(dir, &args)?;
+        let _lock = lock_cfg()?;
         let mut cfg = restore_cfg()?;
         cfg.add_contract(contract.into(), args)?;
         Ok(())
     }
 
     pub fn remove_contract(contract: &str) -> eyre::Result<()> {
+        let _lock = lock_cfg()?;
         let mut cfg = restore_cfg()?;
         cfg.remove_contract(contract.into())?;
         Ok(())
     }
 
-    pub fn list() {
-        Config::list();
+    pub fn list() -> eyre::Result<()> {
+        Config::list()
+    }
+
+    // report every problem of the config, an error if there is any
+    pub fn check_config() -> eyre::Result<()> {
+        let problems = validate(&restore_cfg()?);
+        for problem in problems.iter() {
+            println!("{}", problem);
+        }
+        match problems.len() {
+            0 => {
+                println!("Config is valid");
+                Ok(())
+            }
+            len => Err(eyre::eyre!("{} problems found in the config", len)),
+        }
+    }
+
+    // names of every network profile
+    pub fn network_names() -> eyre::Result<Vec<String>> {
+        Ok(restore_cfg()?.networks.keys().cloned().collect())
     }
 
     pub fn clean() -> eyre::Result<()> {
+        let _lock = lock_cfg()?;
         let mut cfg = restore_cfg()?;
         cfg.clean()?;
         println!("Clean configuration file success");
         Ok(())
     }
 
+    // run the local node until it is stopped, its chain state is dumped on
+    // exit and loaded on the next start
+    pub async fn node(port: Option<u16>) -> eyre::Result<()> {
+        let mut options = match restore_cfg() {
+            Ok(cfg) => cfg.network(Some(LOCAL_NETWORK))?.local.unwrap_or_default(),
+            Err(_) => AnvilOptions::default(),
+        };
+        if port.is_some() {
+            options.port = port;
+        }
+        let state = project_file(INIT_NODE_STATE);
+        if state.exists() {
+            options.load_state = Some(state.to_string_lossy().to_string());
+        }
+        options.dump_state = Some(state.to_string_lossy().to_string());
+
+        let node = Node::new(&options)?;
+        if node.is_alive().await {
+            return Err(eyre::eyre!(
+                "Local node already running on {}",
+                node.rpc_url
+            ));
+        }
+        let mut child = options
+            .command()
+            .spawn()
+            .map_err(|err| eyre::eyre!("Start anvil failed: {}, please install foundry", err))?;
+        node.save()?;
+        println!(
+            "Local node started on {}, state is kept in {}",
+            node.rpc_url,
+            state.display()
+        );
+
+        let status = tokio::task::spawn_blocking(move || child.wait()).await??;
+        Node::remove()?;
+        println!("Local node stopped, {}", status);
+        Ok(())
+    }
+
     pub async fn run(mut self) -> eyre::Result<()> {
         let cfg = restore_cfg()?;
         self.set_config(cfg);
         match !self.cfg.contracts.is_empty() {
             true => {
-                // Todo: optimize memory
-                let anvil = &Anvil::new().spawn();
-                let provider = get_provider(
-                    anvil,
-                    self.cfg.rpc_url.unwrap_or_else(|| "".to_string()),
-                    self.cfg.pri_key.unwrap_or_else(|| "".to_string()),
-                )
-                .await;
-
-                for mut contract in self.cfg.contracts {
-                    contract.run(provider.clone()).await?;
-                    println!("Deploy contract: {} success", contract.contract);
-                }
+                let journal = Mutex::new(restore_journal()?);
+                let name = self.network.as_deref();
+                let network = target_node(self.cfg.network(name)?, name).await?;
+                let mut deployments = vec![];
+                self.deploy_network(name, network, &journal, &mut deployments)
+                    .await?;
                 println!("Deploy contracts success");
-                return Ok(());
+                Ok(())
             }
             false => {
                 println!("No contract to deploy");
@@ -85,25 +201,788 @@ impl Executer {
         }
     }
 
-    pub async fn verify_tx(chain: &str, tx: &str) -> bool {
-        Verify::verify_tx(chain, tx).await.unwrap()
+    // deploy the batch to every network concurrently, each with its own signer
+    // and nonces, a failed network doesn't stop the others. A network on the
+    // chain of an earlier one with the same signer would race it for the
+    // nonces, it is skipped
+    pub async fn run_networks(mut self, names: Vec<String>) -> eyre::Result<()> {
+        let cfg = restore_cfg()?;
+        self.set_config(cfg);
+        if self.cfg.contracts.is_empty() {
+            println!("No contract to deploy");
+            return Ok(());
+        }
+        if names.is_empty() {
+            return Err(eyre::eyre!("No network to deploy, please set"));
+        }
+
+        let mut networks = vec![];
+        let mut signers: Vec<(String, (u64, Address))> = vec![];
+        for name in names {
+            let network = match self.cfg.network(Some(&name)) {
+                Ok(network) => target_node(network, Some(&name)).await,
+                Err(err) => Err(err),
+            };
+            if let Some(signer) = chain_signer(&network).await {
+                if let Some((earlier, _)) = signers.iter().find(|(_, item)| *item == signer) {
+                    println!(
+                        "Skip network {}, it deploys to chain {} as {:?} like {}",
+                        name, signer.0, signer.1, earlier
+                    );
+                    continue;
+                }
+                signers.push((name.clone(), signer));
+            }
+            networks.push((name, network));
+        }
+        let names = networks
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        let journal = Mutex::new(restore_journal()?);
+        let results = future::join_all(networks.into_iter().map(|(name, network)| {
+            let (executer, journal) = (&self, &journal);
+            async move {
+                let mut deployments = vec![];
+                let result = match network {
+                    Ok(network) => {
+                        executer
+                            .deploy_network(Some(name.as_str()), network, journal, &mut deployments)
+                            .await
+                    }
+                    Err(err) => Err(err),
+                };
+                (deployments, result)
+            }
+        }))
+        .await;
+
+        report_networks(&self.cfg.contracts, &names, &results);
+        let failures = results.iter().filter(|(_, result)| result.is_err()).count();
+        match failures {
+            0 => {
+                println!("Deploy contracts to {} networks success", names.len());
+                Ok(())
+            }
+            _ => Err(eyre::eyre!(
+                "{} of {} networks failed",
+                failures,
+                names.len()
+            )),
+        }
+    }
+
+    // deploy the batch to the network, the deployments made before a failure
+    // are recorded and kept in deployments
+    async fn deploy_network(
+        &self,
+        name: Option<&str>,
+        network: Network,
+        journal: &Mutex<Journal>,
+        deployments: &mut Vec<Deployment>,
+    ) -> eyre::Result<()> {
+        let network_name = name.unwrap_or(DEFAULT_NETWORK);
+        let chains = self.cfg.chains();
+        let anvil = network.spawn_local()?;
+        let provider = get_provider(anvil.as_ref(), &network).await?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let confirmations = network.confirmations_on(chains.by_chain_id(chain_id));
+        println!(
+            "Deploy {} to {}, waiting for {} confirmations",
+            network_name,
+            chains.describe(chain_id),
+            confirmations
+        );
+        let eip1559 = chains
+            .by_chain_id(chain_id)
+            .is_some_and(|chain| chain.eip1559);
+        let mut manager = NonceManager::new(provider, self.replace.clone())
+            .with_confirmations(confirmations)
+            .with_eip1559(eip1559)
+            .with_block_subscription();
+
+        for mut contract in self.cfg.contracts.clone() {
+            let receipt =
+                deploy_in_time(&mut contract, &mut manager, network.timeout, confirmations).await?;
+            let deployment = Deployment::new(
+                network_name,
+                chain_id,
+                &contract.contract,
+                &contract.name,
+                &receipt,
+                confirmations,
+            );
+            lock(journal).record(deployment.clone())?;
+            deployments.push(deployment.clone());
+            println!(
+                "Deploy contract: {} to {} success",
+                contract.contract, network_name
+            );
+            // a failed verification doesn't fail the deployment, it can be retried
+            if let Some(verify) = &self.verify {
+                if let Err(err) = verify_deployment(
+                    &mut contract,
+                    &deployment,
+                    &network,
+                    &chains,
+                    verify,
+                    journal,
+                )
+                .await
+                {
+                    println!("Verify contract: {} failed: {}", contract.contract, err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // deploy the contracts to an anvil fork of the network as the configured
+    // deployer, the real chain is untouched and nothing is journaled
+    pub async fn rehearse(mut self, fork_block_number: Option<u64>) -> eyre::Result<()> {
+        let cfg = restore_cfg()?;
+        self.set_config(cfg);
+        if self.cfg.contracts.is_empty() {
+            println!("No contract to rehearse");
+            return Ok(());
+        }
+
+        let network = self.cfg.network(self.network.as_deref())?;
+        let network_name = self.network.as_deref().unwrap_or(DEFAULT_NETWORK);
+        if network.local.is_some() {
+            return Err(eyre::eyre!(
+                "Network {} is local, deploy it without --rehearse",
+                network_name
+            ));
+        }
+        let rpc_urls = network.rpc_urls();
+        let fork_url = rpc_urls
+            .first()
+            .cloned()
+            .ok_or_else(|| eyre::eyre!("Rpc url not set, please set"))?;
+        // pin the fork block so that the rehearsal is reproducible
+        let fork_block_number = match fork_block_number {
+            Some(fork_block_number) => fork_block_number,
+            None => get_rpc_provider(&rpc_urls, network.max_retries, network.quorum)
+                .await?
+                .get_block_number()
+                .await?
+                .as_u64(),
+        };
+        let anvil = AnvilOptions {
+            fork: Some(fork_url),
+            fork_block_number: Some(fork_block_number),
+            ..Default::default()
+        }
+        .spawn();
+        println!(
+            "Rehearse on anvil fork of {} at block {}",
+            network_name, fork_block_number
+        );
+
+        // the fork keeps the chain id, so the deployer signs as on the real chain
+        let fork = Network {
+            rpc_url: Some(anvil.endpoint()),
+            fallback_rpc_urls: vec![],
+            confirmations: Some(1),
+            quorum: 0,
+            ..network
+        };
+        let provider = get_provider(None, &fork).await?;
+        let chains = self.cfg.chains();
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let chain = chains.by_chain_id(chain_id);
+        let deployer = provider.address();
+        let balance = provider.get_balance(deployer, None).await?;
+        // fund the deployer so that the whole batch runs even if the balance is short
+        let funded = balance + U256::exp10(20);
+        provider
+            .provider()
+            .request::<_, ()>("anvil_setBalance", (deployer, funded))
+            .await?;
+
+        let eip1559 = chain.is_some_and(|chain| chain.eip1559);
+        let mut manager = NonceManager::new(provider, self.replace)
+            .with_eip1559(eip1559)
+            .with_block_subscription();
+        let mut gas_used = U256::zero();
+        for mut contract in self.cfg.contracts {
+            let receipt = deploy_in_time(&mut contract, &mut manager, fork.timeout, 1).await?;
+            let used = receipt.gas_used.unwrap_or_default();
+            let cost = used * receipt.effective_gas_price.unwrap_or_default();
+            println!(
+                "Rehearse contract: {}, gas used: {}, cost: {}\n",
+                contract.contract,
+                used,
+                amount(chain, cost)
+            );
+            gas_used += used;
+        }
+
+        let spent = funded - manager.client().get_balance(deployer, None).await?;
+        println!("Total gas used: {}", gas_used);
+        println!("Deployer: {:?}", deployer);
+        println!("Deployer balance: {}", amount(chain, balance));
+        println!("Deployer balance spent: {}", amount(chain, spent));
+        if spent > balance {
+            println!(
+                "Deployer balance is {} short of the deployment",
+                amount(chain, spent - balance)
+            );
+        }
+        println!(
+            "Rehearse contracts success, nothing sent to {}",
+            network_name
+        );
+        Ok(())
+    }
+
+    // write the batch as a Safe Transaction Builder file instead of broadcasting
+    pub async fn export_safe(
+        mut self,
+        file: &str,
+        chain_id: Option<u64>,
+        safe_address: Option<String>,
+        salt: Option<H256>,
+    ) -> eyre::Result<()> {
+        let cfg = restore_cfg()?;
+        self.set_config(cfg);
+        if self.cfg.contracts.is_empty() {
+            println!("No contract to export");
+            return Ok(());
+        }
+
+        let network = self.cfg.network(self.network.as_deref())?;
+        let chain_id = match chain_id {
+            Some(chain_id) => chain_id,
+            None => {
+                let rpc_urls = network.rpc_urls();
+                if rpc_urls.is_empty() {
+                    return Err(eyre::eyre!(
+                        "Rpc url not set, please set or provide --chain-id"
+                    ));
+                }
+                get_rpc_provider(&rpc_urls, network.max_retries, network.quorum)
+                    .await?
+                    .get_chainid()
+                    .await?
+                    .as_u64()
+            }
+        };
+        network.check_chain_id(chain_id)?;
+
+        let mut batch = SafeBatch::new(chain_id, safe_address);
+        for mut contract in self.cfg.contracts {
+            let data = contract.deployment_data().await?;
+            let address = batch.add_deployment(data, salt);
+            match address {
+                Some(address) => println!(
+                    "Export contract: {}:{}, predicted address: {:?}",
+                    contract.contract, contract.name, address
+                ),
+                None => println!("Export contract: {}:{}", contract.contract, contract.name),
+            }
+            // the calls follow the deployment, made by the Safe
+            if contract.calls.is_empty() {
+                continue;
+            }
+            let address = address.ok_or_else(|| {
+                eyre::eyre!(
+                    "Calls of {}:{} need --salt to predict the contract address",
+                    contract.contract,
+                    contract.name
+                )
+            })?;
+            for call in contract.calls.iter() {
+                batch.add_call(address, contract.calldata(call)?);
+                println!(
+                    "Export call: {} of {}:{}",
+                    call.function, contract.contract, contract.name
+                );
+            }
+        }
+        batch.save(file)?;
+        println!("Export safe transactions batch to {} success", file);
+        Ok(())
+    }
+
+    // sign every contract deployment offline with consecutive nonces
+    pub async fn sign_only(
+        mut self,
+        file: &str,
+        nonce: u64,
+        chain_id: u64,
+        gas_price: U256,
+        gas_limit: U256,
+    ) -> eyre::Result<()> {
+        let cfg = restore_cfg()?;
+        self.set_config(cfg);
+        if self.cfg.contracts.is_empty() {
+            println!("No contract to sign");
+            return Ok(());
+        }
+
+        let network = self.cfg.network(self.network.as_deref())?;
+        network.check_chain_id(chain_id)?;
+        let pri_key = network
+            .pri_key
+            .ok_or_else(|| eyre::eyre!("Private key not set, please set"))?;
+        let wallet = get_from_private_key(&pri_key)?.with_chain_id(chain_id);
+
+        // the calls would be broadcast as contract creations
+        if let Some(contract) = self
+            .cfg
+            .contracts
+            .iter()
+            .find(|contract| !contract.calls.is_empty())
+        {
+            return Err(eyre::eyre!(
+                "Calls of {}:{} can't be signed offline, deploy without --sign-only",
+                contract.contract,
+                contract.name
+            ));
+        }
+
+        let mut batch = SignedBatch::new(chain_id);
+        for (idx, mut contract) in self.cfg.contracts.into_iter().enumerate() {
+            let tx = TransactionRequest::new()
+                .from(wallet.address())
+                .data(contract.deployment_data().await?)
+                .nonce(nonce + idx as u64)
+                .gas(gas_limit)
+                .gas_price(gas_price);
+            let signed = batch.sign(
+                &wallet,
+                contract.contract.clone(),
+                contract.name.clone(),
+                tx,
+            )?;
+            println!(
+                "Sign contract: {}:{}, nonce: {}, tx hash: {:?}",
+                contract.contract, contract.name, signed.nonce, signed.hash
+            );
+        }
+        batch.save(file)?;
+        println!("Save signed transactions to {} success", file);
+        Ok(())
+    }
+
+    // submit signed raw transactions and wait for their receipts
+    pub async fn broadcast(self, file: &str) -> eyre::Result<()> {
+        let batch = SignedBatch::load(file)?;
+        let cfg = restore_cfg()?;
+        let network = cfg.network(self.network.as_deref())?;
+        let provider =
+            get_rpc_provider(&network.rpc_urls(), network.max_retries, network.quorum).await?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        network.check_chain_id(chain_id)?;
+        if chain_id != batch.chain_id {
+            return Err(eyre::eyre!(
+                "Signed transactions are for chain id {}, but rpc url is chain id {}",
+                batch.chain_id,
+                chain_id
+            ));
+        }
+        let confirmations = network.confirmations_on(cfg.chains().by_chain_id(chain_id));
+
+        let mut journal = restore_journal()?;
+        for signed in batch.transactions {
+            println!("Broadcast contract: {}:{}", signed.contract, signed.name);
+            let receipt = provider
+                .send_raw_transaction(signed.raw)
+                .await?
+                .confirmations(confirmations)
+                .await?
+                .ok_or_else(|| eyre::eyre!("Transaction {:?} dropped", signed.hash))?;
+            check_deployed(&provider, &receipt)
+                .await
+                .wrap_err_with(|| {
+                    format!("Failed to deploy {}:{}", signed.contract, signed.name)
+                })?;
+            report_receipt(&receipt);
+            journal.record(Deployment::new(
+                self.network.as_deref().unwrap_or(DEFAULT_NETWORK),
+                chain_id,
+                &signed.contract,
+                &signed.name,
+                &receipt,
+                confirmations,
+            ))?;
+        }
+        println!("Broadcast transactions success");
+        Ok(())
+    }
+
+    // replace the pending transaction at the nonce with a 0-value self transfer
+    pub async fn cancel(self, nonce: u64) -> eyre::Result<()> {
+        let name = self.network.as_deref();
+        let network = target_node(restore_cfg()?.network(name)?, name).await?;
+        let anvil = network.spawn_local()?;
+        let provider = get_provider(anvil.as_ref(), &network).await?;
+        let manager = NonceManager::new(provider, self.replace).with_block_subscription();
+        let receipt = manager.cancel(nonce.into()).await?;
+        println!(
+            "Cancel nonce {} success, transaction hash: {:?}",
+            nonce, receipt.transaction_hash
+        );
+        Ok(())
+    }
+
+    // verify the source of a journaled deployment on the explorer of its chain
+    pub async fn verify_contract(self, contract: &str, options: VerifyOptions) -> eyre::Result<()> {
+        let target = ContractInfo::new(contract.to_string(), vec![]);
+        let cfg = restore_cfg()?;
+        let network = cfg.network(self.network.as_deref())?;
+        let mut contract = cfg
+            .contracts
+            .iter()
+            .find(|item| item.contract == target.contract && item.name == target.name)
+            .cloned()
+            .ok_or_else(|| eyre::eyre!("Contract {} not added, please add", contract))?;
+        let chain_id = rpc_chain_id(&network).await?;
+        let chains = cfg.chains();
+        let journal = Mutex::new(restore_journal()?);
+        let deployment = lock(&journal)
+            .find(chain_id, &contract.contract, &contract.name)
+            .cloned()
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "Contract {}:{} not deployed on {}",
+                    contract.contract,
+                    contract.name,
+                    chains.describe(chain_id)
+                )
+            })?;
+        match verify_deployment(
+            &mut contract,
+            &deployment,
+            &network,
+            &chains,
+            &options,
+            &journal,
+        )
+        .await?
+        {
+            true => Ok(()),
+            false => Err(eyre::eyre!("Verify contract: {} failed", contract.contract)),
+        }
+    }
+
+    // compare the code of every journaled deployment of the network with the
+    // runtime bytecode of its artifact
+    pub async fn check(self) -> eyre::Result<()> {
+        let cfg = restore_cfg()?;
+        let name = self.network.as_deref();
+        let network = target_node(cfg.network(name)?, name).await?;
+        let provider =
+            get_rpc_provider(&network.rpc_urls(), network.max_retries, network.quorum).await?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        network.check_chain_id(chain_id)?;
+        let deployments = restore_journal()?.on_chain(chain_id);
+        if deployments.is_empty() {
+            println!("No deployment to check");
+            return Ok(());
+        }
+
+        let mut mismatches = 0;
+        for deployment in deployments.iter() {
+            let code = provider.get_code(deployment.address, None).await?;
+            let mut contract = cfg
+                .contracts
+                .iter()
+                .find(|item| item.contract == deployment.contract && item.name == deployment.name)
+                .cloned()
+                .unwrap_or_else(|| {
+                    ContractInfo::new(
+                        format!("{}:{}", deployment.contract, deployment.name),
+                        vec![],
+                    )
+                });
+            contract.compiler = cfg.compiler.clone();
+            // the artifact of the deployment, unless recompiled since
+            contract.load().await?;
+            let result = if code.as_ref().is_empty() {
+                "no code"
+            } else if bytecode_matches(
+                code.as_ref(),
+                contract.runtime_bytecode.as_ref(),
+                &contract.immutables,
+            ) {
+                "match"
+            } else {
+                "mismatch"
+            };
+            if result != "match" {
+                mismatches += 1;
+            }
+            println!(
+                "Check contract: {}:{} at {:?} {}",
+                deployment.contract, deployment.name, deployment.address, result
+            );
+        }
+
+        match mismatches {
+            0 => {
+                println!("Check contracts success");
+                Ok(())
+            }
+            _ => Err(eyre::eyre!(
+                "{} of {} contracts don't match the local compilation",
+                mismatches,
+                deployments.len()
+            )),
+        }
+    }
+
+    // read the transaction result from the node of the network
+    pub async fn verify_tx_by_rpc(self, tx: H256) -> eyre::Result<bool> {
+        let cfg = restore_cfg()?;
+        let name = self.network.as_deref();
+        let network = target_node(cfg.network(name)?, name).await?;
+        let provider =
+            get_rpc_provider(&network.rpc_urls(), network.max_retries, network.quorum).await?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        network.check_chain_id(chain_id)?;
+        let report = Verify::verify_tx_by_rpc(&provider, tx).await?;
+        report_tx(&report, cfg.chains().by_chain_id(chain_id));
+        Ok(report.success)
+    }
+
+    // verify the transaction and the contract of every journaled deployment of
+    // the network, at most concurrency of them at a time
+    pub async fn verify_all(self, concurrency: usize) -> eyre::Result<()> {
+        let cfg = restore_cfg()?;
+        let name = self.network.as_deref();
+        let network = target_node(cfg.network(name)?, name).await?;
+        let network_name = self.network.as_deref().unwrap_or(DEFAULT_NETWORK);
+        let provider =
+            get_rpc_provider(&network.rpc_urls(), network.max_retries, network.quorum).await?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        network.check_chain_id(chain_id)?;
+        let deployments = restore_journal()?.on_chain(chain_id);
+        if deployments.is_empty() {
+            return Err(eyre::eyre!(
+                "No deployment recorded on {}",
+                cfg.chains().describe(chain_id)
+            ));
+        }
+
+        let statuses = stream::iter(deployments.iter())
+            .map(|deployment| Verify::verify_deployment(&provider, deployment))
+            .buffered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        println!(
+            "Deployments of {} on {}",
+            network_name,
+            cfg.chains().describe(chain_id)
+        );
+        println!(
+            "{:<40} {:<44} {:<10} Status",
+            "Contract", "Address", "Block"
+        );
+        let mut failures = 0;
+        for (deployment, status) in deployments.iter().zip(statuses.iter()) {
+            if *status != RecordStatus::Verified {
+                failures += 1;
+            }
+            let status = match status {
+                RecordStatus::Verified => "verified".to_string(),
+                RecordStatus::Failed(reason) => format!("failed, {}", reason),
+                RecordStatus::Missing(reason) => format!("missing, {}", reason),
+            };
+            println!(
+                "{:<40} {:<44} {:<10} {}",
+                format!("{}:{}", short_path(&deployment.contract), deployment.name),
+                format!("{:?}", deployment.address),
+                deployment.block_number,
+                status
+            );
+        }
+
+        match failures {
+            0 => {
+                println!("Verify {} deployments success", deployments.len());
+                Ok(())
+            }
+            _ => Err(eyre::eyre!(
+                "{} of {} deployments failed or missing",
+                failures,
+                deployments.len()
+            )),
+        }
+    }
+
+    pub async fn verify_tx(chain: &str, tx: &str) -> eyre::Result<bool> {
+        Verify::verify_tx(chain, tx).await
+    }
+}
+
+// deploy the contract, failing if it is not confirmed within the network timeout
+async fn deploy_in_time<M>(
+    contract: &mut ContractInfo,
+    manager: &mut NonceManager<M>,
+    timeout: u64,
+    confirmations: usize,
+) -> eyre::Result<TransactionReceipt>
+where
+    M: Middleware + 'static,
+    M::Provider: PubsubClient,
+{
+    // compiled before the timer starts, the timeout only covers sending the
+    // creation and waiting for its confirmations
+    let data = contract.deployment_data().await?;
+    tokio::time::timeout(Duration::from_secs(timeout), contract.deploy(data, manager))
+        .await
+        .map_err(|_| {
+            eyre::eyre!(
+                "Deploy contract: {} timeout after {}s waiting for {} confirmations",
+                contract.contract,
+                timeout,
+                confirmations
+            )
+        })?
+}
+
+// chain id of the rpc url of the network, which must be the declared one
+async fn rpc_chain_id(network: &Network) -> eyre::Result<u64> {
+    let chain_id = get_rpc_provider(&network.rpc_urls(), network.max_retries, network.quorum)
+        .await?
+        .get_chainid()
+        .await?
+        .as_u64();
+    network.check_chain_id(chain_id)?;
+    Ok(chain_id)
+}
+
+// contract path to report, relative to the project root, or the file name
+// of a contract outside of it, which is kept absolute
+fn short_path(contract: &str) -> &str {
+    let path = Path::new(contract);
+    if path.is_relative() {
+        return contract;
+    }
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(contract)
+}
+
+// verify the source of the deployment and record the result in the journal
+async fn verify_deployment(
+    contract: &mut ContractInfo,
+    deployment: &Deployment,
+    network: &Network,
+    chains: &ChainRegistry,
+    options: &VerifyOptions,
+    journal: &Mutex<Journal>,
+) -> eyre::Result<bool> {
+    let chain = chains.find(options.chain.as_deref(), deployment.chain_id)?;
+    let explorer = Explorer::for_network(network, chain, deployment.chain_id)?;
+    let request = VerifyRequest::new(contract, deployment.address, options).await?;
+    println!(
+        "Verify contract: {}:{} at {:?} on {}",
+        deployment.contract,
+        deployment.name,
+        deployment.address,
+        explorer.api_url()
+    );
+    let verified = match explorer.verify(&request).await? {
+        VerifyStatus::Verified => {
+            println!("Verify contract: {} success", deployment.contract);
+            true
+        }
+        VerifyStatus::Pending => {
+            println!("Verify contract: {} still pending", deployment.contract);
+            false
+        }
+        VerifyStatus::Failed(reason) => {
+            println!(
+                "Verify contract: {} failed: {}",
+                deployment.contract, reason
+            );
+            false
+        }
+    };
+    lock(journal).set_verified(deployment, verified)?;
+    Ok(verified)
+}
+
+// the journal is shared by the networks deployed concurrently, each record is
+// saved before the lock is released
+fn lock(journal: &Mutex<Journal>) -> MutexGuard<'_, Journal> {
+    journal.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// chain id and signer address of the network, None if local or unreachable
+async fn chain_signer(network: &eyre::Result<Network>) -> Option<(u64, Address)> {
+    let network = network
+        .as_ref()
+        .ok()
+        .filter(|network| network.local.is_none())?;
+    let provider = get_provider(None, network).await.ok()?;
+    let chain_id = provider.get_chainid().await.ok()?;
+    Some((chain_id.as_u64(), provider.address()))
+}
+
+// matrix of the addresses of every contract on every network, followed by the
+// failures of the networks
+fn report_networks(
+    contracts: &[ContractInfo],
+    names: &[String],
+    results: &[(Vec<Deployment>, eyre::Result<()>)],
+) {
+    print!("{:<40}", "Contract");
+    for name in names {
+        print!(" {:<44}", name);
+    }
+    println!();
+    for contract in contracts {
+        print!(
+            "{:<40}",
+            format!("{}:{}", short_path(&contract.contract), contract.name)
+        );
+        for (deployments, result) in results {
+            let deployment = deployments
+                .iter()
+                .find(|item| item.contract == contract.contract && item.name == contract.name);
+            let cell = match (deployment, result) {
+                (Some(deployment), _) => format!("{:?}", deployment.address),
+                (None, Err(_)) => "failed".to_string(),
+                (None, Ok(_)) => "-".to_string(),
+            };
+            print!(" {:<44}", cell);
+        }
+        println!();
+    }
+    for (name, (_, result)) in names.iter().zip(results.iter()) {
+        if let Err(err) = result {
+            println!("Network {} failed: {}", name, err);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::project::{enter_test_root, TempRoot};
+    use ethers::utils::{hex, Anvil};
     use std::time::Duration;
     use tokio::time::timeout;
 
     mod util {
-        use std::{fs, u8};
+        use std::fs;
+        use std::path::Path;
 
+        use crate::project::TempRoot;
         use crate::utils::fs::*;
 
-        pub fn create_sol_files(num: u8) -> std::io::Result<()> {
+        // copies of examples/contract.sol in the dir, as contract0.sol
+        pub fn create_sol_files(dir: &Path, num: u8) -> std::io::Result<()> {
             for i in 0..num {
-                let dst = String::new() + "examples/contract" + &i.to_string() + ".sol";
+                let dst = dir.join(format!("contract{}.sol", i));
+                let dst = dst.to_string_lossy().to_string();
                 if is_existed(&dst) {
                     println!("{} already exists", dst);
                     continue;
@@ -113,53 +992,209 @@ mod tests {
             Ok(())
         }
 
-        pub fn delete_sol_files(num: u8) -> std::io::Result<()> {
-            for i in 0..num {
-                let dst = String::new() + "examples/contract" + &i.to_string() + ".sol";
-                if is_existed(&dst) {
-                    fs::remove_file(dst.as_str()).unwrap();
-                }
-            }
-            Ok(())
-        }
-
         #[test]
         pub fn test_create_sol_files() {
-            create_sol_files(100).unwrap();
-            delete_sol_files(100).unwrap();
+            let root = TempRoot::new();
+            create_sol_files(&root.path, 100).unwrap();
+            assert!(root.path.join("contract99.sol").exists());
         }
     }
 
     #[tokio::test]
     async fn test_batch_contracts_run_success() {
         let num: u8 = 100;
-        util::delete_sol_files(num).unwrap();
         // given
-        util::create_sol_files(num).unwrap();
+        let root = TempRoot::new();
+        util::create_sol_files(&root.path, num).unwrap();
 
-        // changed to local anvil rpc
         let mut cfg = Config {
             rpc_url: Some("".to_string()),
             pri_key: Some("".to_string()),
             contracts: vec![],
+            ..Default::default()
         };
 
         for i in 0..num {
-            let contract =
-                String::new() + "examples/contract" + &i.to_string() + ".sol:SimpleStorage";
+            let contract = root.path.join(format!("contract{}.sol:SimpleStorage", i));
+            let contract = contract.to_string_lossy().to_string();
             let args = String::new() + "value" + &i.to_string();
             cfg.add_contract(contract, vec![args]).unwrap();
         }
 
         // when
-        let mut executer = Executer::new();
+        let mut executer = Executer::new().with_network(Some(LOCAL_NETWORK.to_string()));
         executer.set_config(cfg.clone());
         timeout(Duration::from_millis(10000), executer.run())
             .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_add_contract() {
+        // given
+        let root = TempRoot::new();
+        save(&Config::new()).unwrap();
+
+        // when
+        let handles = (0..8)
+            .map(|i| {
+                let path = root.path.clone();
+                std::thread::spawn(move || {
+                    enter_test_root(&path);
+                    let contract = format!("examples/contract.sol:Contract{}", i);
+                    Executer::add_contract(&path, &contract, vec![]).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // then no change is lost
+        assert_eq!(restore_cfg().unwrap().contracts.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_run_networks() {
+        // given
+        let _root = TempRoot::new();
+        let mut cfg = Config {
+            rpc_url: Some("".to_string()),
+            pri_key: Some("".to_string()),
+            ..Default::default()
+        };
+        cfg.add_contract(
+            "examples/contract.sol:SimpleStorage".to_string(),
+            vec!["value".into()],
+        )
+        .unwrap();
+        for (name, chain_id) in [("a", 1337), ("b", 1338)] {
+            let update = NetworkUpdate {
+                local: Some(AnvilOptions {
+                    chain_id: Some(chain_id),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            cfg.set_network(name.into(), update).unwrap();
+        }
+        // without rpc url and private key
+        cfg.set_network("down".into(), NetworkUpdate::default())
+            .unwrap();
+
+        // when
+        let names = vec!["a".to_string(), "b".to_string(), "down".to_string()];
+        let result = Executer::new().run_networks(names).await;
+
+        // then
+        let journal = restore_journal().unwrap();
+        assert!(result.is_err());
+        assert_eq!(journal.on_chain(1337).len(), 1);
+        assert_eq!(journal.on_chain(1338).len(), 1);
+        assert_ne!(
+            journal.on_chain(1337)[0].network,
+            journal.on_chain(1338)[0].network
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_networks_same_signer() {
+        // given
+        let _root = TempRoot::new();
+        let anvil = Anvil::new().spawn();
+        let mut cfg = Config::default();
+        cfg.add_contract(
+            "examples/contract.sol:SimpleStorage".to_string(),
+            vec!["value".into()],
+        )
+        .unwrap();
+        let pri_key = hex::encode(anvil.keys()[0].to_be_bytes());
+        for name in ["a", "b"] {
+            let update = NetworkUpdate {
+                rpc_url: Some(anvil.endpoint()),
+                pri_key: Some(pri_key.clone()),
+                ..Default::default()
+            };
+            cfg.set_network(name.into(), update).unwrap();
+        }
+
+        // when
+        let names = vec!["a".to_string(), "b".to_string()];
+        let result = Executer::new().run_networks(names).await;
+
+        // then the contracts are deployed once, by the first network
+        let journal = restore_journal().unwrap();
+        assert!(result.is_ok());
+        assert_eq!(journal.on_chain(31337).len(), 1);
+        assert_eq!(journal.on_chain(31337)[0].network, "a");
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_reverted() {
+        // given
+        let root = TempRoot::new();
+        let anvil = Anvil::new().spawn();
+        let mut cfg = Config::default();
+        let update = NetworkUpdate {
+            rpc_url: Some(anvil.endpoint()),
+            ..Default::default()
+        };
+        cfg.set_network("anvil".into(), update).unwrap();
+        let wallet: LocalWallet = anvil.keys()[0].clone().into();
+        let mut batch = SignedBatch::new(31337);
+        // creation code of PUSH1 0 PUSH1 0 REVERT
+        let tx = TransactionRequest::new()
+            .data(hex::decode("60006000fd").unwrap())
+            .nonce(0u64)
+            .gas(100000u64)
+            .gas_price(U256::exp10(10));
+        batch
+            .sign(&wallet, "examples/reverts.sol".into(), "Reverts".into(), tx)
             .unwrap();
+        let file = root.path.join("reverted_batch.json");
+        let file = file.to_str().unwrap();
+        batch.save(file).unwrap();
+
+        // when
+        let result = Executer::new()
+            .with_network(Some("anvil".into()))
+            .broadcast(file)
+            .await;
+
+        // then the reverted creation is not journaled
+        assert!(format!("{:?}", result.unwrap_err()).contains("reverted"));
+        assert!(restore_journal()
+            .unwrap()
+            .find(31337, "examples/reverts.sol", "Reverts")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rehearse_leaves_chain_untouched() {
+        // given
+        let _root = TempRoot::new();
+        let anvil = Anvil::new().spawn();
+        let mut cfg = Config {
+            rpc_url: Some(anvil.endpoint()),
+            pri_key: Some(hex::encode(anvil.keys()[0].to_be_bytes())),
+            ..Default::default()
+        };
+        cfg.add_contract(
+            "examples/contract.sol:SimpleStorage".to_string(),
+            vec!["value".into()],
+        )
+        .unwrap();
 
-        // then clean
-        util::delete_sol_files(num).unwrap();
-        Executer::clean().unwrap();
+        // when
+        Executer::new().rehearse(None).await.unwrap();
+
+        // then
+        let provider = get_rpc_provider(&[anvil.endpoint()], 0, 0).await.unwrap();
+        let count = provider
+            .get_transaction_count(anvil.addresses()[0], None)
+            .await
+            .unwrap();
+        assert_eq!(count, 0.into());
     }
 }
@@ -0,0 +1,631 @@
+use ethers::prelude::*;
+use ethers::utils::hex;
+use eyre::eyre;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::chain::ChainInfo;
+use crate::config::Network;
+use crate::contract::{CompilerSettings, ContractInfo};
+use crate::retry::{retry, DEFAULT_MAX_RETRIES};
+
+// the explorer must compile the verified source with the settings of the
+// project, the solc default runs are sent when the optimizer is disabled
+static OPTIMIZER_RUNS: usize = 200;
+static POLL_INTERVAL: Duration = Duration::from_secs(5);
+static MAX_POLLS: usize = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CodeFormat {
+    // the contract file, which must not import other files or be flattened
+    SingleFile,
+    // the contract file and the files it imports
+    StandardJson,
+}
+
+impl FromStr for CodeFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single-file" => Ok(CodeFormat::SingleFile),
+            "standard-json" => Ok(CodeFormat::StandardJson),
+            _ => Err(eyre!("Unknown code format {}", s)),
+        }
+    }
+}
+
+impl CodeFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CodeFormat::SingleFile => "solidity-single-file",
+            CodeFormat::StandardJson => "solidity-standard-json-input",
+        }
+    }
+}
+
+// how deployed contracts are verified
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifyOptions {
+    // explorer chain name, found by the chain id of the deployment if not set
+    pub chain: Option<String>,
+    pub code_format: CodeFormat,
+    // the version of the local solc if not set
+    pub compiler_version: Option<String>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions {
+            chain: None,
+            code_format: CodeFormat::StandardJson,
+            compiler_version: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum VerifyStatus {
+    Pending,
+    Verified,
+    Failed(String),
+}
+
+// source verification request of a deployed contract
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifyRequest {
+    pub address: Address,
+    pub contract_name: String,
+    pub source_code: String,
+    pub code_format: CodeFormat,
+    pub compiler_version: String,
+    pub optimization_used: bool,
+    pub runs: usize,
+    // abi encoded constructor args without 0x
+    pub constructor_arguments: String,
+}
+
+impl VerifyRequest {
+    pub async fn new(
+        contract: &mut ContractInfo,
+        address: Address,
+        options: &VerifyOptions,
+    ) -> eyre::Result<Self> {
+        let constructor_arguments = hex::encode(contract.constructor_args().await?);
+        let (source_code, contract_name) = match options.code_format {
+            CodeFormat::SingleFile => (
+                fs::read_to_string(&contract.contract)?,
+                contract.name.clone(),
+            ),
+            CodeFormat::StandardJson => (
+                standard_json(&contract.contract, &contract.compiler)?,
+                format!("{}:{}", contract.contract, contract.name),
+            ),
+        };
+        let compiler_version = match &options.compiler_version {
+            Some(compiler_version) => compiler_version.clone(),
+            None => compiler_version(&contract.compiler.solc())?,
+        };
+        Ok(VerifyRequest {
+            address,
+            contract_name,
+            source_code,
+            code_format: options.code_format,
+            compiler_version,
+            optimization_used: contract.compiler.optimizer_runs.is_some(),
+            runs: contract.compiler.optimizer_runs.unwrap_or(OPTIMIZER_RUNS),
+            constructor_arguments,
+        })
+    }
+
+    fn form(&self, api_key: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("apikey", api_key.to_string()),
+            ("module", "contract".to_string()),
+            ("action", "verifysourcecode".to_string()),
+            ("contractaddress", format!("{:?}", self.address)),
+            ("sourceCode", self.source_code.clone()),
+            ("codeformat", self.code_format.as_str().to_string()),
+            ("contractname", self.contract_name.clone()),
+            ("compilerversion", self.compiler_version.clone()),
+            (
+                "optimizationUsed",
+                (self.optimization_used as u8).to_string(),
+            ),
+            ("runs", self.runs.to_string()),
+            // misspelled by the etherscan api
+            ("constructorArguements", self.constructor_arguments.clone()),
+        ]
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response<T = String> {
+    status: String,
+    result: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReceiptStatus {
+    // 1 for success, 0 for failure and empty if the transaction is unknown
+    status: String,
+}
+
+// etherscan family explorer api
+pub struct Explorer {
+    api_url: String,
+    api_key: String,
+    client: reqwest::Client,
+    poll_interval: Duration,
+}
+
+impl Explorer {
+    pub fn new(api_url: &str, api_key: &str) -> Self {
+        Explorer {
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            client: reqwest::Client::new(),
+            poll_interval: POLL_INTERVAL,
+        }
+    }
+
+    // the explorer url and api key set on the network, otherwise the explorer
+    // of the chain and the api key in its env
+    pub fn for_network(
+        network: &Network,
+        chain: Option<&ChainInfo>,
+        chain_id: u64,
+    ) -> eyre::Result<Self> {
+        Explorer::with_keys(network, chain, chain_id, |name| env::var(name).ok())
+    }
+
+    // as for_network, with the api key of the chain looked up by its env name
+    pub fn with_keys(
+        network: &Network,
+        chain: Option<&ChainInfo>,
+        chain_id: u64,
+        keys: impl Fn(&str) -> Option<String>,
+    ) -> eyre::Result<Self> {
+        let api_url = match (&network.explorer_url, chain) {
+            (Some(api_url), _) => api_url.clone(),
+            (None, Some(chain)) => chain.explorer_url.clone().ok_or_else(|| {
+                eyre!(
+                    "{} chain explorer not set, please set explorer url",
+                    chain.name
+                )
+            })?,
+            (None, None) => {
+                return Err(eyre!(
+                    "No explorer for chain id {}, please set explorer url or provide --chain",
+                    chain_id
+                ))
+            }
+        };
+        let api_key = match (&network.explorer_api_key, chain) {
+            (Some(api_key), _) => api_key.clone(),
+            (None, Some(chain)) => api_key(chain, keys)?,
+            (None, None) => return Err(eyre!("Explorer api key not set, please set")),
+        };
+        Ok(Explorer::new(&api_url, &api_key))
+    }
+
+    // the explorer and the api key of the chain
+    pub fn for_chain(chain: &ChainInfo) -> eyre::Result<Self> {
+        Explorer::for_network(&Network::default(), Some(chain), chain.chain_id)
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    // submit the source and wait until the explorer has verified it
+    pub async fn verify(&self, request: &VerifyRequest) -> eyre::Result<VerifyStatus> {
+        let guid = match self.submit(request).await? {
+            Some(guid) => guid,
+            None => return Ok(VerifyStatus::Verified),
+        };
+        for _ in 0..MAX_POLLS {
+            tokio::time::sleep(self.poll_interval).await;
+            match self.check(&guid).await? {
+                VerifyStatus::Pending => continue,
+                status => return Ok(status),
+            }
+        }
+        Ok(VerifyStatus::Pending)
+    }
+
+    // the guid of the verification, None if the contract is already verified
+    pub async fn submit(&self, request: &VerifyRequest) -> eyre::Result<Option<String>> {
+        let form = request.form(&self.api_key);
+        for _ in 0..MAX_POLLS {
+            let response = retry(DEFAULT_MAX_RETRIES, || async {
+                self.client
+                    .post(&self.api_url)
+                    .form(&form)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<Response>()
+                    .await
+            })
+            .await?;
+            let result = response.result.to_lowercase();
+            if response.status == "1" {
+                return Ok(Some(response.result));
+            } else if result.contains("already verified") {
+                return Ok(None);
+            } else if result.contains("unable to locate contractcode") {
+                // the explorer has not indexed the deployment yet
+                tokio::time::sleep(self.poll_interval).await;
+                continue;
+            }
+            return Err(eyre!("Submit verification failed: {}", response.result));
+        }
+        Err(eyre!("Explorer has not indexed {:?}", request.address))
+    }
+
+    pub async fn check(&self, guid: &str) -> eyre::Result<VerifyStatus> {
+        let query = [
+            ("apikey", self.api_key.as_str()),
+            ("module", "contract"),
+            ("action", "checkverifystatus"),
+            ("guid", guid),
+        ];
+        let response = retry(DEFAULT_MAX_RETRIES, || async {
+            self.client
+                .get(&self.api_url)
+                .query(&query)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Response>()
+                .await
+        })
+        .await?;
+        let result = response.result.to_lowercase();
+        let status = if result.contains("pending") {
+            VerifyStatus::Pending
+        } else if result.contains("pass") || result.contains("already verified") {
+            VerifyStatus::Verified
+        } else {
+            VerifyStatus::Failed(response.result)
+        };
+        Ok(status)
+    }
+
+    // whether the transaction is mined successfully, false if it failed or is unknown
+    pub async fn tx_status(&self, tx: &str) -> eyre::Result<bool> {
+        let query = [
+            ("apikey", self.api_key.as_str()),
+            ("module", "transaction"),
+            ("action", "gettxreceiptstatus"),
+            ("txhash", tx),
+        ];
+        let response = retry(DEFAULT_MAX_RETRIES, || async {
+            self.client
+                .get(&self.api_url)
+                .query(&query)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Response<serde_json::Value>>()
+                .await
+        })
+        .await?;
+        if response.status != "1" {
+            return Err(eyre!(
+                "Check transaction status failed: {}",
+                response.result
+            ));
+        }
+        let receipt: ReceiptStatus = serde_json::from_value(response.result)?;
+        Ok(receipt.status == "1")
+    }
+}
+
+// explorer api key of the chain from its env
+pub fn api_key(chain: &ChainInfo, keys: impl Fn(&str) -> Option<String>) -> eyre::Result<String> {
+    let name = chain.api_key_env.as_deref().ok_or_else(|| {
+        eyre!(
+            "{} chain api key env not set, please set explorer api key",
+            chain.name
+        )
+    })?;
+    match keys(name) {
+        Some(api_key) if !api_key.is_empty() => Ok(api_key),
+        _ => Err(eyre!(
+            "{} chain explorer api key not set, please set {} or explorer api key of the network",
+            chain.name,
+            name
+        )),
+    }
+}
+
+// long version of the solc as the explorers expect, v0.8.15+commit.e14f2714
+pub fn compiler_version(solc: &Solc) -> eyre::Result<String> {
+    let version = solc.version()?;
+    let commit = version
+        .build
+        .as_str()
+        .split('.')
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(".");
+    if !commit.starts_with("commit.") {
+        return Err(eyre!(
+            "Unknown commit of solc {}, please provide --compiler-version",
+            version
+        ));
+    }
+    Ok(format!(
+        "v{}.{}.{}+{}",
+        version.major, version.minor, version.patch, commit
+    ))
+}
+
+// standard json input of the contract file and the files it imports, keyed
+// by the source names solc compiled them with
+fn standard_json(path: &str, compiler: &CompilerSettings) -> eyre::Result<String> {
+    let mut sources = BTreeMap::new();
+    collect_sources(Path::new(path), &mut sources)?;
+    let sources = sources
+        .into_iter()
+        .map(|(name, content)| (name, json!({ "content": content })))
+        .collect::<serde_json::Map<_, _>>();
+    let input = json!({
+        "language": "Solidity",
+        "sources": sources,
+        "settings": {
+            "optimizer": {
+                "enabled": compiler.optimizer_runs.is_some(),
+                "runs": compiler.optimizer_runs.unwrap_or(OPTIMIZER_RUNS),
+            },
+            "outputSelection": { "*": { "*": ["*"] } },
+        },
+    });
+    Ok(input.to_string())
+}
+
+fn collect_sources(path: &Path, sources: &mut BTreeMap<String, String>) -> eyre::Result<()> {
+    let name = path.to_string_lossy().to_string();
+    if sources.contains_key(&name) {
+        return Ok(());
+    }
+    let content =
+        fs::read_to_string(path).map_err(|err| eyre!("Read source {} failed: {}", name, err))?;
+    let imports = imports(&content);
+    sources.insert(name, content);
+
+    for import in imports {
+        // relative imports are resolved from the importing file, the others
+        // from the base path
+        let import = match import.starts_with("./") || import.starts_with("../") {
+            true => normalize(&path.parent().unwrap_or_else(|| Path::new("")).join(import)),
+            false => PathBuf::from(import),
+        };
+        collect_sources(&import, sources)?;
+    }
+    Ok(())
+}
+
+// paths of the import directives of a solidity source
+fn imports(source: &str) -> Vec<String> {
+    source
+        .split(';')
+        .map(|statement| {
+            statement
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with("//"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|statement| statement.starts_with("import "))
+        .filter_map(|statement| {
+            let start = statement.find(['"', '\''])?;
+            let quote = &statement[start..start + 1];
+            let end = statement[start + 1..].find(quote)?;
+            Some(statement[start + 1..start + 1 + end].to_string())
+        })
+        .collect()
+}
+
+// resolve . and .. the way solc does for source names, without touching the
+// file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ChainRegistry;
+    use crate::project::TempRoot;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = vec![];
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut chunk).await.unwrap();
+            request.extend_from_slice(&chunk[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            let end = match text.find("\r\n\r\n") {
+                Some(end) => end + 4,
+                None if n == 0 => return text,
+                None => continue,
+            };
+            let content_length = text
+                .lines()
+                .find_map(|line| {
+                    line.to_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|length| length.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            if n == 0 || request.len() >= end + content_length {
+                return text;
+            }
+        }
+    }
+
+    // explorer which accepts every submission and verifies it after a pending check
+    async fn mock_explorer() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}/api", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut checks = 0;
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_request(&mut stream).await;
+                let body = if request.contains("action=verifysourcecode") {
+                    assert!(request.contains("constructorArguements="));
+                    r#"{"status":"1","message":"OK","result":"guid"}"#
+                } else if request.contains("action=gettxreceiptstatus") {
+                    match request.contains("txhash=0x01") {
+                        true => r#"{"status":"1","message":"OK","result":{"status":"1"}}"#,
+                        false => r#"{"status":"1","message":"OK","result":{"status":""}}"#,
+                    }
+                } else {
+                    checks += 1;
+                    match checks {
+                        1 => r#"{"status":"0","message":"NOTOK","result":"Pending in queue"}"#,
+                        _ => r#"{"status":"1","message":"OK","result":"Pass - Verified"}"#,
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        api_url
+    }
+
+    #[test]
+    fn test_imports() {
+        let source = r#"
+            // SPDX-License-Identifier: MIT
+            pragma solidity ^0.8.0;
+            import "./Token.sol";
+            // import "./Commented.sol";
+            import {Ownable} from '../access/Ownable.sol';
+            import {
+                A,
+                B
+            } from "lib/AB.sol";
+            contract C {}
+        "#;
+        assert_eq!(
+            imports(source),
+            vec!["./Token.sol", "../access/Ownable.sol", "lib/AB.sol"]
+        );
+        assert_eq!(
+            normalize(Path::new("contracts/token/../access/./Ownable.sol")),
+            PathBuf::from("contracts/access/Ownable.sol")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_with_mock_explorer() {
+        // given
+        let _root = TempRoot::new();
+        let network = Network {
+            explorer_url: Some(mock_explorer().await),
+            explorer_api_key: Some("key".into()),
+            ..Default::default()
+        };
+        let explorer = Explorer::for_network(&network, None, 31337)
+            .unwrap()
+            .with_poll_interval(Duration::from_millis(10));
+        let mut contract = ContractInfo::new(
+            "examples/contract.sol:SimpleStorage".to_string(),
+            vec!["value".into()],
+        );
+        let options = VerifyOptions {
+            compiler_version: Some("v0.8.15+commit.e14f2714".into()),
+            ..Default::default()
+        };
+        let request = VerifyRequest::new(&mut contract, Address::random(), &options)
+            .await
+            .unwrap();
+
+        // when
+        let status = explorer.verify(&request).await.unwrap();
+
+        // then
+        assert_eq!(status, VerifyStatus::Verified);
+        assert_eq!(request.contract_name, "examples/contract.sol:SimpleStorage");
+        assert!(request.source_code.contains("examples/contract.sol"));
+    }
+
+    #[test]
+    fn test_explorer_api_key() {
+        // given
+        let network = Network::default();
+        let chains = ChainRegistry::default();
+        let avalanche = chains.by_chain_id(43114);
+        let no_keys = |_: &str| None;
+        let keys = |name: &str| (name == "SNOWTRACE_API_KEY").then(|| "key".to_string());
+
+        // then the api key must be set
+        assert!(Explorer::with_keys(&network, avalanche, 43114, no_keys).is_err());
+        assert!(Explorer::with_keys(&network, None, 31337, keys).is_err());
+
+        // when
+        let explorer = Explorer::with_keys(&network, avalanche, 43114, keys).unwrap();
+
+        // then
+        assert_eq!(explorer.api_url(), "https://api.snowtrace.io/api");
+        assert_eq!(explorer.api_key, "key");
+        let fuji = chains.by_name("avalanche-fuji");
+        let explorer = Explorer::with_keys(&network, fuji, 31337, keys).unwrap();
+        assert_eq!(explorer.api_url(), "https://api-testnet.snowtrace.io/api");
+    }
+
+    #[tokio::test]
+    async fn test_tx_status_on_custom_chain() {
+        // given
+        let chain = ChainInfo {
+            explorer_url: Some(mock_explorer().await),
+            api_key_env: Some("MOCKSCAN_API_KEY".into()),
+            ..ChainInfo::new("mock", 1337)
+        };
+        let network = Network::default();
+        assert!(Explorer::with_keys(&network, Some(&chain), 1337, |_| None).is_err());
+
+        // when
+        let keys = |name: &str| (name == "MOCKSCAN_API_KEY").then(|| "key".to_string());
+        let explorer = Explorer::with_keys(&network, Some(&chain), 1337, keys).unwrap();
+        let success = explorer.tx_status("0x01").await.unwrap();
+        let unknown = explorer.tx_status("0x02").await.unwrap();
+
+        // then
+        assert!(success);
+        assert!(!unknown);
+    }
+}
@@ -0,0 +1,199 @@
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::project::project_file;
+use crate::utils::fs::*;
+use crate::{INIT_JOURNAL, INIT_JOURNAL_LOCK};
+
+// deployment journal, records every confirmed contract deployment
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Journal {
+    pub deployments: Vec<Deployment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub network: String,
+    pub chain_id: u64,
+    pub contract: String,
+    pub name: String,
+    pub address: Address,
+    pub tx_hash: H256,
+    pub block_number: u64,
+    pub confirmations: usize,
+    // whether the source is verified on the explorer, None if not submitted
+    #[serde(default)]
+    pub verified: Option<bool>,
+}
+
+pub fn save(journal: &Journal) -> eyre::Result<()> {
+    create_project_dir()?;
+    let json = serde_json::to_string_pretty(journal)?;
+    write_atomic(&project_file(INIT_JOURNAL), &json)?;
+    Ok(())
+}
+
+pub fn restore_journal() -> eyre::Result<Journal> {
+    let path = project_file(INIT_JOURNAL);
+    if !path.exists() {
+        return Ok(Journal::default());
+    }
+    let json = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+impl Deployment {
+    pub fn new(
+        network: &str,
+        chain_id: u64,
+        contract: &str,
+        name: &str,
+        receipt: &TransactionReceipt,
+        confirmations: usize,
+    ) -> Self {
+        Deployment {
+            network: network.to_string(),
+            chain_id,
+            contract: contract.to_string(),
+            name: name.to_string(),
+            address: receipt.contract_address.unwrap_or_default(),
+            tx_hash: receipt.transaction_hash,
+            block_number: receipt.block_number.unwrap_or_default().as_u64(),
+            confirmations,
+            verified: None,
+        }
+    }
+}
+
+impl Journal {
+    // apply the change to the latest journal on disk and save it, under the
+    // journal lock so that the deployments recorded meanwhile by other morge
+    // processes are kept
+    fn update(&mut self, change: impl FnOnce(&mut Journal)) -> eyre::Result<()> {
+        let _lock = lock_exclusive(&project_file(INIT_JOURNAL_LOCK))?;
+        *self = restore_journal()?;
+        change(self);
+        save(self)
+    }
+
+    // record the deployment and save the journal, the latest deployment of the
+    // same contract on the same chain replaces the previous one, whichever
+    // network profile it was deployed with
+    pub fn record(&mut self, deployment: Deployment) -> eyre::Result<()> {
+        self.update(|journal| journal.add(deployment))
+    }
+
+    fn add(&mut self, deployment: Deployment) {
+        self.deployments.retain(|item| {
+            !(item.chain_id == deployment.chain_id
+                && item.contract == deployment.contract
+                && item.name == deployment.name)
+        });
+        self.deployments.push(deployment);
+    }
+
+    pub fn find(&self, chain_id: u64, contract: &str, name: &str) -> Option<&Deployment> {
+        self.deployments.iter().find(|item| {
+            item.chain_id == chain_id && item.contract == contract && item.name == name
+        })
+    }
+
+    // the deployments recorded on the chain
+    pub fn on_chain(&self, chain_id: u64) -> Vec<Deployment> {
+        self.deployments
+            .iter()
+            .filter(|item| item.chain_id == chain_id)
+            .cloned()
+            .collect()
+    }
+
+    // record the verification result of the deployment and save the journal
+    pub fn set_verified(&mut self, deployment: &Deployment, verified: bool) -> eyre::Result<()> {
+        self.update(|journal| {
+            for item in journal.deployments.iter_mut() {
+                if item.chain_id == deployment.chain_id
+                    && item.contract == deployment.contract
+                    && item.name == deployment.name
+                {
+                    item.verified = Some(verified);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::TempRoot;
+
+    fn deployment(network: &str, chain_id: u64, block_number: u64) -> Deployment {
+        Deployment {
+            network: network.to_string(),
+            chain_id,
+            contract: "examples/contract.sol".to_string(),
+            name: "SimpleStorage".to_string(),
+            address: Address::random(),
+            tx_hash: H256::random(),
+            block_number,
+            confirmations: 1,
+            verified: None,
+        }
+    }
+
+    #[test]
+    fn test_record_deployment() {
+        // given
+        let _root = TempRoot::new();
+        let mut journal = Journal::default();
+
+        // when
+        journal.record(deployment("default", 1, 1)).unwrap();
+        journal.record(deployment("polygon", 137, 2)).unwrap();
+        journal.record(deployment("default", 1, 3)).unwrap();
+        // another profile of the same chain
+        journal.record(deployment("mainnet", 1, 4)).unwrap();
+
+        // then
+        let journal = restore_journal().unwrap();
+        assert_eq!(journal.deployments.len(), 2);
+        assert_eq!(journal.deployments[0].network, "polygon");
+        assert_eq!(journal.deployments[1].block_number, 4);
+        assert_eq!(journal.on_chain(1).len(), 1);
+        assert!(journal.on_chain(56).is_empty());
+        let mut journal = journal;
+        let deployment = journal
+            .find(137, "examples/contract.sol", "SimpleStorage")
+            .cloned()
+            .unwrap();
+        journal.set_verified(&deployment, true).unwrap();
+        assert_eq!(
+            restore_journal().unwrap().deployments[0].verified,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_record_keeps_other_records() {
+        // given two morge runs which read the journal before either recorded
+        let _root = TempRoot::new();
+        let mut first = restore_journal().unwrap();
+        let mut second = restore_journal().unwrap();
+
+        // when
+        first.record(deployment("default", 1, 1)).unwrap();
+        second.record(deployment("polygon", 137, 2)).unwrap();
+        let recorded = first
+            .find(1, "examples/contract.sol", "SimpleStorage")
+            .cloned();
+        first.set_verified(&recorded.unwrap(), true).unwrap();
+
+        // then no deployment is lost
+        let journal = restore_journal().unwrap();
+        assert_eq!(journal.deployments.len(), 2);
+        assert_eq!(journal.on_chain(1)[0].verified, Some(true));
+        assert_eq!(journal.on_chain(137).len(), 1);
+        assert_eq!(first, journal);
+    }
+}
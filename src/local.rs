@@ -0,0 +1,387 @@
+use ethers::prelude::*;
+use ethers::signers::coins_bip39::English;
+use ethers::utils::{hex, Anvil, AnvilInstance};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::process::Command;
+
+use crate::config::Network;
+use crate::project::project_file;
+use crate::utils::fs::*;
+use crate::utils::parse::get_rpc_provider;
+use crate::INIT_NODE;
+
+// network which deploys to the running node, or a throwaway anvil node
+// without rpc url and private key
+pub static LOCAL_NETWORK: &str = "local";
+// port of the long-lived node when not set
+pub static NODE_PORT: u16 = 8545;
+// anvil nodes spawned per run are killed on drop and never write the dump
+pub static DUMP_STATE_UNSUPPORTED: &str =
+    "dump state is not supported by the anvil node spawned for each run";
+// mnemonic of the anvil accounts when not set
+static DEFAULT_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+// options of the anvil node spawned for a local network, anvil defaults are
+// used for the ones not set
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnvilOptions {
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    // seconds between blocks, mines on every transaction if not set
+    #[serde(default)]
+    pub block_time: Option<u64>,
+    #[serde(default)]
+    pub accounts: Option<u64>,
+    #[serde(default)]
+    pub mnemonic: Option<String>,
+    // file to dump the chain state to on exit, only set by morge node
+    #[serde(default)]
+    pub dump_state: Option<String>,
+    // file to load the chain state from on start
+    #[serde(default)]
+    pub load_state: Option<String>,
+    // rpc url of the chain to fork
+    #[serde(default)]
+    pub fork: Option<String>,
+    // block to fork at, the latest one if not set
+    #[serde(default)]
+    pub fork_block_number: Option<u64>,
+}
+
+impl AnvilOptions {
+    pub fn anvil(&self) -> Anvil {
+        let mut anvil = Anvil::new();
+        if let Some(port) = self.port {
+            anvil = anvil.port(port);
+        }
+        if let Some(block_time) = self.block_time {
+            anvil = anvil.block_time(block_time);
+        }
+        if let Some(mnemonic) = &self.mnemonic {
+            anvil = anvil.mnemonic(mnemonic);
+        }
+        if let Some(fork) = &self.fork {
+            anvil = anvil.fork(fork);
+        }
+        if let Some(fork_block_number) = self.fork_block_number {
+            anvil = anvil.args([
+                "--fork-block-number".to_string(),
+                fork_block_number.to_string(),
+            ]);
+        }
+        anvil.args(self.args())
+    }
+
+    pub fn spawn(&self) -> AnvilInstance {
+        self.anvil().spawn()
+    }
+
+    // command of a long-lived anvil node, which runs in the foreground
+    pub fn command(&self) -> Command {
+        let mut command = Command::new("anvil");
+        command
+            .arg("--port")
+            .arg(self.port.unwrap_or(NODE_PORT).to_string());
+        if let Some(block_time) = self.block_time {
+            command.arg("--block-time").arg(block_time.to_string());
+        }
+        if let Some(mnemonic) = &self.mnemonic {
+            command.arg("--mnemonic").arg(mnemonic);
+        }
+        if let Some(fork) = &self.fork {
+            command.arg("--fork-url").arg(fork);
+        }
+        if let Some(fork_block_number) = self.fork_block_number {
+            command
+                .arg("--fork-block-number")
+                .arg(fork_block_number.to_string());
+        }
+        command.args(self.args());
+        command
+    }
+
+    // anvil flags without a builder method
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(chain_id) = self.chain_id {
+            args.extend(["--chain-id".to_string(), chain_id.to_string()]);
+        }
+        if let Some(accounts) = self.accounts {
+            args.extend(["--accounts".to_string(), accounts.to_string()]);
+        }
+        if let Some(dump_state) = &self.dump_state {
+            args.extend(["--dump-state".to_string(), dump_state.clone()]);
+        }
+        if let Some(load_state) = &self.load_state {
+            args.extend(["--load-state".to_string(), load_state.clone()]);
+        }
+        args
+    }
+
+    // the options set in other replace the ones here
+    pub fn merge(&mut self, other: AnvilOptions) {
+        if other.port.is_some() {
+            self.port = other.port;
+        }
+        if other.chain_id.is_some() {
+            self.chain_id = other.chain_id;
+        }
+        if other.block_time.is_some() {
+            self.block_time = other.block_time;
+        }
+        if other.accounts.is_some() {
+            self.accounts = other.accounts;
+        }
+        if other.mnemonic.is_some() {
+            self.mnemonic = other.mnemonic;
+        }
+        if other.dump_state.is_some() {
+            self.dump_state = other.dump_state;
+        }
+        if other.load_state.is_some() {
+            self.load_state = other.load_state;
+        }
+        if other.fork.is_some() {
+            self.fork = other.fork;
+        }
+        if other.fork_block_number.is_some() {
+            self.fork_block_number = other.fork_block_number;
+        }
+    }
+}
+
+// the running morge node, local networks deploy to it instead of a throwaway anvil
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub rpc_url: String,
+    // key of the first account of the node
+    pub pri_key: String,
+}
+
+impl Node {
+    pub fn new(options: &AnvilOptions) -> eyre::Result<Self> {
+        let mnemonic = options.mnemonic.as_deref().unwrap_or(DEFAULT_MNEMONIC);
+        let wallet = MnemonicBuilder::<English>::default()
+            .phrase(mnemonic)
+            .build()?;
+        Ok(Node {
+            rpc_url: format!("http://localhost:{}", options.port.unwrap_or(NODE_PORT)),
+            pri_key: hex::encode(wallet.signer().to_bytes()),
+        })
+    }
+
+    pub fn save(&self) -> eyre::Result<()> {
+        create_project_dir()?;
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(project_file(INIT_NODE), json)?;
+        Ok(())
+    }
+
+    pub fn load() -> eyre::Result<Option<Self>> {
+        let path = project_file(INIT_NODE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    pub fn remove() -> eyre::Result<()> {
+        let path = project_file(INIT_NODE);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub async fn is_alive(&self) -> bool {
+        match get_rpc_provider(std::slice::from_ref(&self.rpc_url), 0, 0).await {
+            Ok(provider) => provider.get_chainid().await.is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+// a local network, or the default one when no network is given and neither
+// the rpc url nor the private key is set, targets the running node if there
+// is one. The node file is left behind when the node is killed so it must
+// answer too
+pub async fn target_node(network: Network, name: Option<&str>) -> eyre::Result<Network> {
+    let unset = name.is_none()
+        && network.rpc_urls().is_empty()
+        && network.pri_key.as_deref().unwrap_or_default().is_empty();
+    if network.local.is_none() && !unset {
+        return Ok(network);
+    }
+    match Node::load()? {
+        Some(node) if node.is_alive().await => {
+            println!("Deploy to local node {}", node.rpc_url);
+            Ok(Network {
+                rpc_url: Some(node.rpc_url),
+                fallback_rpc_urls: vec![],
+                pri_key: Some(node.pri_key),
+                quorum: 0,
+                local: None,
+                ..network
+            })
+        }
+        _ => Ok(network),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Network;
+    use crate::project::TempRoot;
+    use crate::utils::parse::get_provider;
+
+    #[test]
+    fn test_merge_options() {
+        // given
+        let mut options = AnvilOptions {
+            port: Some(8545),
+            chain_id: Some(1337),
+            ..Default::default()
+        };
+
+        // when
+        options.merge(AnvilOptions {
+            chain_id: Some(1338),
+            accounts: Some(3),
+            ..Default::default()
+        });
+
+        // then
+        assert_eq!(options.port, Some(8545));
+        assert_eq!(options.chain_id, Some(1338));
+        assert_eq!(
+            options.args(),
+            vec!["--chain-id", "1338", "--accounts", "3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_network() {
+        // given
+        let network = Network {
+            local: Some(AnvilOptions {
+                chain_id: Some(1337),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // when
+        let anvil = network.spawn_local().unwrap();
+        let client = get_provider(anvil.as_ref(), &network).await.unwrap();
+
+        // then
+        assert_eq!(client.get_chainid().await.unwrap(), 1337.into());
+    }
+
+    #[tokio::test]
+    async fn test_chain_id_mismatch() {
+        // given
+        let network = Network {
+            chain_id: Some(137),
+            local: Some(AnvilOptions {
+                chain_id: Some(56),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // when
+        let anvil = network.spawn_local().unwrap();
+        let err = get_provider(anvil.as_ref(), &network).await.unwrap_err();
+
+        // then
+        assert!(err.to_string().contains("mismatch"));
+        let network = Network {
+            chain_id: Some(56),
+            ..network
+        };
+        assert!(get_provider(anvil.as_ref(), &network).await.is_ok());
+    }
+
+    #[test]
+    fn test_dump_state_rejected() {
+        let network = Network {
+            local: Some(AnvilOptions {
+                dump_state: Some("state.json".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let err = network.spawn_local().err().unwrap();
+        assert!(err.to_string().contains("morge node"));
+    }
+
+    #[test]
+    fn test_node_key() {
+        let anvil = Anvil::new().spawn();
+        let node = Node::new(&AnvilOptions::default()).unwrap();
+        assert_eq!(node.pri_key, hex::encode(anvil.keys()[0].to_be_bytes()));
+        assert_eq!(node.rpc_url, "http://localhost:8545");
+    }
+
+    #[tokio::test]
+    async fn test_target_node() {
+        // given
+        let _root = TempRoot::new();
+        let anvil = Anvil::new().spawn();
+        let node = Node {
+            rpc_url: anvil.endpoint(),
+            pri_key: hex::encode(anvil.keys()[0].to_be_bytes()),
+        };
+        let local = Network {
+            local: Some(AnvilOptions::default()),
+            ..Default::default()
+        };
+
+        let remote = Network {
+            rpc_url: Some("http://localhost:8546".into()),
+            ..Default::default()
+        };
+
+        // when
+        node.save().unwrap();
+        let network = target_node(local.clone(), Some(LOCAL_NETWORK))
+            .await
+            .unwrap();
+        let default = target_node(Network::default(), None).await.unwrap();
+        let unset = target_node(Network::default(), Some("polygon"))
+            .await
+            .unwrap();
+        let configured = target_node(remote.clone(), None).await.unwrap();
+        drop(anvil);
+        let stale = target_node(local.clone(), Some(LOCAL_NETWORK))
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(network.rpc_url, Some(node.rpc_url.clone()));
+        assert!(network.local.is_none());
+        assert_eq!(default.rpc_url, Some(node.rpc_url));
+        assert_eq!(unset, Network::default());
+        assert_eq!(configured, remote);
+        assert_eq!(stale, local);
+    }
+
+    #[tokio::test]
+    async fn test_remote_network_without_rpc_url_or_key() {
+        let network = Network::default();
+        assert!(network.spawn_local().unwrap().is_none());
+        assert!(get_provider(None, &network).await.is_err());
+
+        let network = Network {
+            rpc_url: Some("http://localhost:8545".into()),
+            ..Default::default()
+        };
+        assert!(get_provider(None, &network).await.is_err());
+    }
+}
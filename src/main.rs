@@ -1,62 +1,299 @@
 // use morge::log_config;
-use morge::{args::cli, Executer};
-use std::{env, ffi::OsString};
+use clap::ArgMatches;
+use ethers::{types::H256, utils::parse_units};
+use morge::{
+    args::cli,
+    chain::ChainInfo,
+    config::NetworkUpdate,
+    explorer::VerifyOptions,
+    local::AnvilOptions,
+    nonce::{ReplaceOptions, MIN_FEE_BUMP},
+    project, Executer,
+};
+use std::{ffi::OsString, path::Path, str::FromStr, time::Duration};
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     // log_config()?;
     let matches = cli().get_matches();
+    // init creates the project where it is run, the others find it upward
+    let cwd = project::enter(
+        matches.value_of("root"),
+        matches.value_of("config"),
+        matches.subcommand_name() != Some("init"),
+    )?;
 
     match matches.subcommand() {
         Some(("init", _sub_matches)) => {
             Executer::init()?;
         }
-        Some(("set", sub_matches)) => {
-            let rpc_url = sub_matches.value_of("rpc-url").expect("set rpc failed");
-            let pri_key = sub_matches
-                .value_of("private-key")
-                .expect("set private key failed");
+        Some(("set", sub_matches)) => match sub_matches.value_of("network") {
+            Some(network) => {
+                let update = NetworkUpdate {
+                    rpc_url: sub_matches.value_of("rpc-url").map(String::from),
+                    fallback_rpc_urls: sub_matches
+                        .values_of("fallback-rpc-url")
+                        .map(|urls| urls.map(String::from).collect()),
+                    pri_key: sub_matches.value_of("private-key").map(String::from),
+                    chain_id: sub_matches
+                        .value_of("expected-chain-id")
+                        .map(|chain_id| chain_id.parse::<u64>())
+                        .transpose()?,
+                    confirmations: sub_matches
+                        .value_of("confirmations")
+                        .map(|confirmations| confirmations.parse::<usize>())
+                        .transpose()?,
+                    timeout: sub_matches
+                        .value_of("timeout")
+                        .map(|timeout| timeout.parse::<u64>())
+                        .transpose()?,
+                    max_retries: sub_matches
+                        .value_of("max-retries")
+                        .map(|max_retries| max_retries.parse::<u32>())
+                        .transpose()?,
+                    quorum: sub_matches
+                        .value_of("quorum")
+                        .map(|quorum| quorum.parse::<usize>())
+                        .transpose()?,
+                    local: anvil_options(sub_matches)?,
+                    explorer_url: sub_matches.value_of("explorer-url").map(String::from),
+                    explorer_api_key: sub_matches.value_of("explorer-api-key").map(String::from),
+                };
 
-            Executer::set_rpc_and_key(rpc_url, pri_key)?;
+                Executer::set_network(network, update)?;
+            }
+            None => {
+                let rpc_url = sub_matches.value_of("rpc-url").expect("set rpc failed");
+                let pri_key = sub_matches
+                    .value_of("private-key")
+                    .expect("set private key failed");
+
+                Executer::set_rpc_and_key(rpc_url, pri_key)?;
+            }
+        },
+        Some(("set-chain", sub_matches)) => {
+            let chain = ChainInfo {
+                symbol: sub_matches
+                    .value_of("symbol")
+                    .expect("get symbol failed")
+                    .to_string(),
+                explorer_url: sub_matches.value_of("explorer-url").map(String::from),
+                api_key_env: sub_matches.value_of("api-key-env").map(String::from),
+                eip1559: sub_matches.is_present("eip1559"),
+                confirmations: sub_matches
+                    .value_of("confirmations")
+                    .expect("get confirmations failed")
+                    .parse::<usize>()?,
+                ..ChainInfo::new(
+                    sub_matches.value_of("name").expect("get name failed"),
+                    sub_matches
+                        .value_of("chain-id")
+                        .expect("get chain id failed")
+                        .parse::<u64>()?,
+                )
+            };
+
+            Executer::set_chain(chain)?;
         }
         Some(("add", sub_matches)) => {
-            let contract = env::current_dir()?.to_str().unwrap().to_string()
-                + "/"
-                + sub_matches
+            let contract = contract_path(
+                &cwd,
+                sub_matches
                     .value_of("contract")
-                    .expect("get sol file failed");
+                    .expect("get sol file failed"),
+            )?;
             let args = sub_matches
                 .get_many::<String>("args")
                 .into_iter()
                 .flatten()
-                .map(|item| format!("{item:?}"))
+                .cloned()
                 .collect::<Vec<String>>();
 
-            Executer::add_contract(&contract, args)?;
+            Executer::add_contract(&cwd, &contract, args)?;
         }
         Some(("remove", sub_matches)) => {
-            let contract = env::current_dir()?.to_str().unwrap().to_string()
-                + "/"
-                + sub_matches
+            let contract = contract_path(
+                &cwd,
+                sub_matches
                     .value_of("contract")
-                    .expect("get sol file failed");
+                    .expect("get sol file failed"),
+            )?;
 
             Executer::remove_contract(&contract)?;
         }
-        Some(("deploy", _sub_matches)) => {
-            println!("Start deploy");
-            let executor = Executer::new();
-            executor.run().await?;
+        Some(("deploy", sub_matches)) => {
+            let chain_id = sub_matches
+                .value_of("chain-id")
+                .map(|chain_id| chain_id.parse::<u64>())
+                .transpose()?;
+
+            let networks = match sub_matches.is_present("all") {
+                true => Executer::network_names()?,
+                false => sub_matches
+                    .value_of("network")
+                    .map(|networks| {
+                        networks
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|network| !network.is_empty())
+                            .map(String::from)
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default(),
+            };
+            let multiple = sub_matches.is_present("all") || networks.len() > 1;
+            if multiple
+                && ["safe", "sign-only", "rehearse"]
+                    .iter()
+                    .any(|arg| sub_matches.is_present(arg))
+            {
+                return Err(eyre::eyre!(
+                    "Deploy to several networks with --safe, --sign-only or --rehearse is not supported"
+                ));
+            }
+
+            let executor = Executer::new()
+                .with_network(networks.first().cloned())
+                .with_replace_options(replace_options(sub_matches)?)
+                .with_verify(match sub_matches.is_present("verify") {
+                    true => Some(verify_options(sub_matches)?),
+                    false => None,
+                });
+            if sub_matches.is_present("sign-only") {
+                println!("Start sign transactions");
+                let file = sub_matches.value_of("out").expect("get out file failed");
+                let nonce = sub_matches
+                    .value_of("nonce")
+                    .expect("get nonce failed")
+                    .parse::<u64>()?;
+                let gas_price = parse_units(
+                    sub_matches
+                        .value_of("gas-price")
+                        .expect("get gas price failed"),
+                    "gwei",
+                )?;
+                let gas_limit = sub_matches
+                    .value_of("gas-limit")
+                    .expect("get gas limit failed")
+                    .parse::<u64>()?;
+                executor
+                    .sign_only(
+                        file,
+                        nonce,
+                        chain_id.expect("get chain id failed"),
+                        gas_price,
+                        gas_limit.into(),
+                    )
+                    .await?;
+                return Ok(());
+            }
+
+            if sub_matches.is_present("rehearse") {
+                println!("Start rehearse deploy");
+                let fork_block_number = sub_matches
+                    .value_of("fork-block-number")
+                    .map(|fork_block_number| fork_block_number.parse::<u64>())
+                    .transpose()?;
+                executor.rehearse(fork_block_number).await?;
+                return Ok(());
+            }
+
+            match sub_matches.value_of("safe") {
+                Some(file) => {
+                    println!("Start export safe transactions");
+                    let safe_address = sub_matches.value_of("safe-address").map(String::from);
+                    let salt = sub_matches
+                        .value_of("salt")
+                        .map(H256::from_str)
+                        .transpose()?;
+                    executor
+                        .export_safe(file, chain_id, safe_address, salt)
+                        .await?;
+                }
+                None if multiple => {
+                    println!("Start deploy to {}", networks.join(", "));
+                    executor.run_networks(networks).await?;
+                }
+                None => {
+                    println!("Start deploy");
+                    executor.run().await?;
+                }
+            }
+        }
+        Some(("cancel", sub_matches)) => {
+            let nonce = sub_matches
+                .value_of("nonce")
+                .expect("get nonce failed")
+                .parse::<u64>()?;
+            println!("Start cancel nonce {}", nonce);
+            let executor = Executer::new()
+                .with_network(sub_matches.value_of("network").map(String::from))
+                .with_replace_options(replace_options(sub_matches)?);
+            executor.cancel(nonce).await?;
+        }
+        Some(("broadcast", sub_matches)) => {
+            let file = sub_matches.value_of("file").expect("get file failed");
+            println!("Start broadcast {}", file);
+            let executor =
+                Executer::new().with_network(sub_matches.value_of("network").map(String::from));
+            executor.broadcast(file).await?;
+        }
+        Some(("verify-contract", sub_matches)) => {
+            let contract = contract_path(
+                &cwd,
+                sub_matches
+                    .value_of("contract")
+                    .expect("get contract failed"),
+            )?;
+            Executer::new()
+                .with_network(sub_matches.value_of("network").map(String::from))
+                .verify_contract(&contract, verify_options(sub_matches)?)
+                .await?;
+        }
+        Some(("verify", sub_matches)) if sub_matches.is_present("all") => {
+            let concurrency = sub_matches
+                .value_of("concurrency")
+                .expect("get concurrency failed")
+                .parse::<usize>()?;
+            Executer::new()
+                .with_network(sub_matches.value_of("network").map(String::from))
+                .verify_all(concurrency)
+                .await?;
         }
         Some(("verify", sub_matches)) => {
-            let chain = sub_matches.value_of("chain").expect("set rpc failed");
             let tx = sub_matches.value_of("tx").expect("get addr failed");
-            println!("Chain: {}, tx: {}", chain, tx);
-            Executer::verify_tx(chain, tx).await;
+            match sub_matches.value_of("chain") {
+                Some(chain) => {
+                    println!("Chain: {}, tx: {}", chain, tx);
+                    Executer::verify_tx(chain, tx).await?;
+                }
+                None => {
+                    Executer::new()
+                        .with_network(sub_matches.value_of("network").map(String::from))
+                        .verify_tx_by_rpc(H256::from_str(tx)?)
+                        .await?;
+                }
+            }
+        }
+        Some(("check", sub_matches)) => {
+            Executer::new()
+                .with_network(sub_matches.value_of("network").map(String::from))
+                .check()
+                .await?;
+        }
+        Some(("check-config", _sub_matches)) => {
+            Executer::check_config()?;
+        }
+        Some(("node", sub_matches)) => {
+            let port = sub_matches
+                .value_of("port")
+                .map(|port| port.parse::<u16>())
+                .transpose()?;
+            Executer::node(port).await?;
         }
         Some(("list", _sub_matches)) => {
             println!("Start list configuration");
-            Executer::list();
+            Executer::list()?;
         }
         Some(("clean", _sub_matches)) => {
             Executer::clean()?;
@@ -73,3 +310,86 @@ async fn main() -> eyre::Result<()> {
     }
     Ok(())
 }
+
+// the file:name contract given from the directory morge was run from, with
+// the file relative to the project root
+fn contract_path(cwd: &Path, contract: &str) -> eyre::Result<String> {
+    match contract.rsplit_once(':') {
+        Some((file, name)) => Ok(format!("{}:{}", project::relative_path(cwd, file)?, name)),
+        None => project::relative_path(cwd, contract),
+    }
+}
+
+fn verify_options(matches: &ArgMatches) -> eyre::Result<VerifyOptions> {
+    Ok(VerifyOptions {
+        chain: matches.value_of("chain").map(String::from),
+        code_format: matches
+            .value_of("code-format")
+            .expect("get code format failed")
+            .parse()?,
+        compiler_version: matches.value_of("compiler-version").map(String::from),
+    })
+}
+
+// the anvil options of a local network, None if neither --local nor any of
+// them is given
+fn anvil_options(matches: &ArgMatches) -> eyre::Result<Option<AnvilOptions>> {
+    let options = AnvilOptions {
+        port: matches
+            .value_of("port")
+            .map(|port| port.parse::<u16>())
+            .transpose()?,
+        chain_id: matches
+            .value_of("chain-id")
+            .map(|chain_id| chain_id.parse::<u64>())
+            .transpose()?,
+        block_time: matches
+            .value_of("block-time")
+            .map(|block_time| block_time.parse::<u64>())
+            .transpose()?,
+        accounts: matches
+            .value_of("accounts")
+            .map(|accounts| accounts.parse::<u64>())
+            .transpose()?,
+        mnemonic: matches.value_of("mnemonic").map(String::from),
+        // kept by morge node only
+        dump_state: None,
+        load_state: matches.value_of("load-state").map(String::from),
+        fork: matches.value_of("fork-url").map(String::from),
+        fork_block_number: matches
+            .value_of("fork-block-number")
+            .map(|fork_block_number| fork_block_number.parse::<u64>())
+            .transpose()?,
+    };
+    match matches.is_present("local") || options != AnvilOptions::default() {
+        true => Ok(Some(options)),
+        false => Ok(None),
+    }
+}
+
+fn replace_options(matches: &ArgMatches) -> eyre::Result<ReplaceOptions> {
+    let timeout = matches
+        .value_of("replace-timeout")
+        .expect("get replace timeout failed")
+        .parse::<u64>()?;
+    let max_replacements = matches
+        .value_of("max-replacements")
+        .expect("get max replacements failed")
+        .parse::<usize>()?;
+    let fee_bump = matches
+        .value_of("fee-bump")
+        .expect("get fee bump failed")
+        .parse::<u64>()?;
+    if fee_bump < MIN_FEE_BUMP {
+        return Err(eyre::eyre!(
+            "Fee bump {}% is too low, nodes reject replacements bumped by less than {}%",
+            fee_bump,
+            MIN_FEE_BUMP
+        ));
+    }
+    Ok(ReplaceOptions {
+        timeout: Duration::from_secs(timeout),
+        max_replacements,
+        fee_bump,
+    })
+}
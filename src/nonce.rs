@@ -0,0 +1,524 @@
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use eyre::eyre;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use std::time::{Duration, Instant};
+
+static POLL_INTERVAL: Duration = Duration::from_millis(500);
+static BLOCK_WAIT: Duration = Duration::from_secs(60);
+// nodes reject replacements bumped by less than 10% as underpriced
+pub static MIN_FEE_BUMP: u64 = 10;
+
+// options to replace transactions which are not mined in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplaceOptions {
+    pub timeout: Duration,
+    pub max_replacements: usize,
+    // percentage to bump the gas price of each replacement
+    pub fee_bump: u64,
+}
+
+impl Default for ReplaceOptions {
+    fn default() -> Self {
+        ReplaceOptions {
+            timeout: Duration::from_secs(120),
+            max_replacements: 3,
+            fee_bump: 15,
+        }
+    }
+}
+
+// subscribes to the new blocks of the node, None if it can't push them
+type SubscribeBlocks<M> = for<'a> fn(&'a M) -> BoxFuture<'a, Option<BoxStream<'a, ()>>>;
+
+// tracks the sender nonces locally instead of relying on the node, and
+// rebroadcasts stuck transactions with the same nonce and a higher gas price
+pub struct NonceManager<M> {
+    client: M,
+    address: Address,
+    nonce: Option<U256>,
+    opts: ReplaceOptions,
+    confirmations: usize,
+    // send EIP-1559 transactions instead of legacy ones
+    eip1559: bool,
+    // blocks are polled if not set
+    subscribe: Option<SubscribeBlocks<M>>,
+}
+
+impl<M: Middleware + 'static> NonceManager<M>
+where
+    M::Provider: PubsubClient,
+{
+    // wait for the blocks pushed by pubsub transports (ws, ipc) instead of
+    // polling, http transports still poll
+    pub fn with_block_subscription(mut self) -> Self {
+        self.subscribe = Some(subscribe_blocks::<M>);
+        self
+    }
+}
+
+impl<M: Middleware + 'static> NonceManager<M> {
+    pub fn new(client: M, opts: ReplaceOptions) -> Self {
+        let address = client
+            .default_sender()
+            .expect("no sender address set for provider");
+        NonceManager {
+            client,
+            address,
+            nonce: None,
+            opts,
+            confirmations: 1,
+            eip1559: false,
+            subscribe: None,
+        }
+    }
+
+    // blocks to wait after the transaction is mined, including its own block
+    pub fn with_confirmations(mut self, confirmations: usize) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    // on chains supporting EIP-1559, as set in the chain registry
+    pub fn with_eip1559(mut self, eip1559: bool) -> Self {
+        self.eip1559 = eip1559;
+        self
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn client(&self) -> &M {
+        &self.client
+    }
+
+    // the next nonce to use, fetched from the pending block the first time
+    pub async fn next_nonce(&mut self) -> eyre::Result<U256> {
+        match self.nonce {
+            Some(nonce) => Ok(nonce),
+            None => {
+                let nonce = self
+                    .client
+                    .get_transaction_count(self.address, Some(BlockNumber::Pending.into()))
+                    .await?;
+                self.nonce = Some(nonce);
+                Ok(nonce)
+            }
+        }
+    }
+
+    // send the transaction with the next local nonce and wait until mined, as
+    // an EIP-1559 transaction priced by the fee estimation of the node if set
+    pub async fn send(&mut self, tx: TransactionRequest) -> eyre::Result<TransactionReceipt> {
+        let nonce = self.next_nonce().await?;
+        let mut tx = tx.from(self.address).nonce(nonce);
+        let tx: TypedTransaction = match (self.eip1559, tx.gas_price) {
+            (true, None) => {
+                let (max_fee, priority_fee) = self.client.estimate_eip1559_fees(None).await?;
+                let mut request = Eip1559TransactionRequest::new()
+                    .from(self.address)
+                    .nonce(nonce)
+                    .max_fee_per_gas(max_fee)
+                    .max_priority_fee_per_gas(priority_fee);
+                request.to = tx.to;
+                request.gas = tx.gas;
+                request.value = tx.value;
+                request.data = tx.data;
+                request.into()
+            }
+            (_, Some(_)) => tx.into(),
+            (false, None) => {
+                tx.gas_price = Some(self.client.get_gas_price().await?);
+                tx.into()
+            }
+        };
+
+        let receipt = self.send_until_mined(tx).await?;
+        // the nonce is used even if the transaction reverted
+        self.nonce = Some(nonce + 1);
+        if receipt.status != Some(1.into()) {
+            return Err(eyre!(
+                "Transaction {:?} with nonce {} reverted in block {}",
+                receipt.transaction_hash,
+                nonce,
+                receipt.block_number.unwrap_or_default()
+            ));
+        }
+        self.wait_confirmations(receipt).await
+    }
+
+    // clear a stuck nonce slot with a 0-value transfer to the sender itself
+    pub async fn cancel(&self, nonce: U256) -> eyre::Result<TransactionReceipt> {
+        let gas_price = self.cancel_gas_price(nonce).await?;
+        let tx = TransactionRequest::new()
+            .from(self.address)
+            .to(self.address)
+            .value(0u64)
+            .nonce(nonce)
+            .gas(21000u64)
+            .gas_price(gas_price);
+        self.send_until_mined(tx.into()).await
+    }
+
+    // gas price replacing the transaction stuck with the nonce, bumped from
+    // the stuck price or the current one, whichever is higher, since the node
+    // rejects a replacement underpriced against the stuck transaction
+    pub async fn cancel_gas_price(&self, nonce: U256) -> eyre::Result<U256> {
+        let current = self.client.get_gas_price().await?;
+        let stuck = self.pending_gas_price(nonce).await.unwrap_or_default();
+        Ok(bump_gas_price(current.max(stuck), self.opts.fee_bump))
+    }
+
+    // gas price of the transaction of the sender waiting in the txpool of the
+    // node with the nonce, None if not found or the node has no txpool api.
+    // Nodes report the max fee of EIP-1559 transactions as their gas price
+    async fn pending_gas_price(&self, nonce: U256) -> Option<U256> {
+        let content = self.client.txpool_content().await.ok()?;
+        let nonce = nonce.to_string();
+        [content.pending, content.queued]
+            .iter()
+            .find_map(|pool| pool.get(&self.address)?.get(&nonce)?.gas_price)
+    }
+
+    async fn send_until_mined(&self, mut tx: TypedTransaction) -> eyre::Result<TransactionReceipt> {
+        let nonce = *tx.nonce().expect("nonce not set for transaction");
+        let mut hashes: Vec<TxHash> = vec![];
+        let mut replacements = 0;
+        loop {
+            match self.client.send_transaction(tx.clone(), None).await {
+                Ok(pending) => hashes.push(*pending),
+                // the previous transaction may be mined while replacing it
+                Err(err) if !hashes.is_empty() => {
+                    if let Some(receipt) = self.find_receipt(&hashes).await? {
+                        return Ok(receipt);
+                    }
+                    return Err(eyre!(
+                        "Replace transaction with nonce {} failed: {}",
+                        nonce,
+                        err
+                    ));
+                }
+                Err(err) => return Err(err.into()),
+            }
+
+            let deadline = Instant::now() + self.opts.timeout;
+            let mut blocks = self.blocks().await;
+            while Instant::now() < deadline {
+                if let Some(receipt) = self.find_receipt(&hashes).await? {
+                    return Ok(receipt);
+                }
+                next_block(
+                    &mut blocks,
+                    deadline.saturating_duration_since(Instant::now()),
+                )
+                .await;
+            }
+
+            if replacements >= self.opts.max_replacements {
+                return Err(eyre!(
+                    "Transaction with nonce {} not mined after {} replacements",
+                    nonce,
+                    replacements
+                ));
+            }
+            replacements += 1;
+
+            let gas_price = bump_fees(&mut tx, self.opts.fee_bump);
+            println!(
+                "Transaction {:?} with nonce {} not mined in {}s, replace {}/{} with gas price {}",
+                hashes.last().unwrap(),
+                nonce,
+                self.opts.timeout.as_secs(),
+                replacements,
+                self.opts.max_replacements,
+                gas_price
+            );
+        }
+    }
+
+    // wait until the block of the receipt is deep enough, refetch the receipt
+    // since the transaction may be moved to another block by a reorg
+    async fn wait_confirmations(
+        &self,
+        mut receipt: TransactionReceipt,
+    ) -> eyre::Result<TransactionReceipt> {
+        let mut blocks = self.blocks().await;
+        loop {
+            let block = receipt.block_number.unwrap_or_default();
+            let current = self.client.get_block_number().await?;
+            if current + 1 >= block + self.confirmations {
+                match self
+                    .client
+                    .get_transaction_receipt(receipt.transaction_hash)
+                    .await?
+                {
+                    Some(latest) if latest.block_number == receipt.block_number => {
+                        return Ok(latest)
+                    }
+                    Some(latest) => {
+                        println!(
+                            "Transaction {:?} moved to block {:?} by reorg",
+                            latest.transaction_hash,
+                            latest.block_number.unwrap_or_default()
+                        );
+                        receipt = latest;
+                    }
+                    None => println!(
+                        "Transaction {:?} reorged out, waiting to be mined again",
+                        receipt.transaction_hash
+                    ),
+                }
+            }
+            next_block(&mut blocks, BLOCK_WAIT).await;
+        }
+    }
+
+    async fn blocks(&self) -> Option<BoxStream<'_, ()>> {
+        match self.subscribe {
+            Some(subscribe) => subscribe(&self.client).await,
+            None => None,
+        }
+    }
+
+    async fn find_receipt(&self, hashes: &[TxHash]) -> eyre::Result<Option<TransactionReceipt>> {
+        for hash in hashes.iter() {
+            if let Some(receipt) = self.client.get_transaction_receipt(*hash).await? {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn subscribe_blocks<M: Middleware>(client: &M) -> BoxFuture<'_, Option<BoxStream<'_, ()>>>
+where
+    M::Provider: PubsubClient,
+{
+    Box::pin(async move {
+        let blocks = client.subscribe_blocks().await.ok()?;
+        Some(blocks.map(|_| ()).boxed())
+    })
+}
+
+// wait for the next block pushed by the subscription of pubsub transports (ws,
+// ipc), or poll with the interval when subscriptions aren't supported (http)
+async fn next_block<S: Stream + Unpin>(blocks: &mut Option<S>, wait: Duration) {
+    let closed = match blocks.as_mut() {
+        Some(stream) => matches!(tokio::time::timeout(wait, stream.next()).await, Ok(None)),
+        None => {
+            tokio::time::sleep(POLL_INTERVAL.min(wait)).await;
+            false
+        }
+    };
+    // subscription closed by the node, fall back to polling
+    if closed {
+        *blocks = None;
+    }
+}
+
+// bump the gas price of a legacy transaction, or both the max fee and the
+// priority fee of an EIP-1559 one since nodes require both to replace it,
+// return the new gas price or max fee
+fn bump_fees(tx: &mut TypedTransaction, fee_bump: u64) -> U256 {
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            let max_fee = bump_gas_price(inner.max_fee_per_gas.unwrap_or_default(), fee_bump);
+            let priority_fee = inner.max_priority_fee_per_gas.unwrap_or_default();
+            inner.max_fee_per_gas = Some(max_fee);
+            inner.max_priority_fee_per_gas = Some(bump_gas_price(priority_fee, fee_bump));
+            max_fee
+        }
+        _ => {
+            let gas_price = bump_gas_price(tx.gas_price().unwrap_or_default(), fee_bump);
+            tx.set_gas_price(gas_price);
+            gas_price
+        }
+    }
+}
+
+pub fn bump_gas_price(gas_price: U256, fee_bump: u64) -> U256 {
+    // round up so that tiny gas prices are still bumped
+    (gas_price * (100 + fee_bump) + 99) / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Network;
+    use crate::utils::parse::get_provider;
+    use ethers::utils::{hex, Anvil};
+
+    #[test]
+    fn test_bump_gas_price() {
+        assert_eq!(bump_gas_price(100.into(), 15), 115.into());
+        assert_eq!(bump_gas_price(1.into(), 10), 2.into());
+        assert_eq!(bump_gas_price(0.into(), 10), 0.into());
+    }
+
+    #[tokio::test]
+    async fn test_send_and_cancel() {
+        // given
+        let anvil = &Anvil::new().spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+        let address = manager.address();
+
+        // when
+        for _ in 0..2 {
+            let tx = TransactionRequest::new().to(address).value(1u64);
+            manager.send(tx).await.unwrap();
+        }
+        let receipt = manager.cancel(2.into()).await.unwrap();
+
+        // then
+        assert_eq!(receipt.to, Some(address));
+        let count = manager
+            .client()
+            .get_transaction_count(address, None)
+            .await
+            .unwrap();
+        assert_eq!(count, 3.into());
+    }
+
+    #[tokio::test]
+    async fn test_send_eip1559() {
+        // given
+        let anvil = &Anvil::new().spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default()).with_eip1559(true);
+        let address = manager.address();
+
+        // when
+        let tx = TransactionRequest::new().to(address).value(1u64);
+        let receipt = manager.send(tx).await.unwrap();
+
+        // then
+        let tx = manager
+            .client()
+            .get_transaction(receipt.transaction_hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tx.transaction_type, Some(2.into()));
+        assert!(tx.max_fee_per_gas.is_some());
+    }
+
+    #[test]
+    fn test_bump_fees() {
+        let mut tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .max_fee_per_gas(100u64)
+            .max_priority_fee_per_gas(10u64)
+            .into();
+        assert_eq!(bump_fees(&mut tx, 10), 110.into());
+        match tx {
+            TypedTransaction::Eip1559(inner) => {
+                assert_eq!(inner.max_priority_fee_per_gas, Some(11.into()))
+            }
+            _ => unreachable!(),
+        }
+        let mut tx: TypedTransaction = TransactionRequest::new().gas_price(100u64).into();
+        assert_eq!(bump_fees(&mut tx, 10), 110.into());
+        assert_eq!(tx.gas_price(), Some(110.into()));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_bumps_stuck_gas_price() {
+        // given a transaction stuck above the current gas price
+        let anvil = &Anvil::new().args(vec!["--no-mining"]).spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let manager = NonceManager::new(client, ReplaceOptions::default());
+        let current = manager.client().get_gas_price().await.unwrap();
+        let stuck = current * 10;
+        let tx = TransactionRequest::new()
+            .to(manager.address())
+            .value(1u64)
+            .nonce(0u64)
+            .gas(21000u64)
+            .gas_price(stuck);
+        manager.client().send_transaction(tx, None).await.unwrap();
+
+        // when
+        let gas_price = manager.cancel_gas_price(0.into()).await.unwrap();
+        let free = manager.cancel_gas_price(1.into()).await.unwrap();
+
+        // then
+        assert_eq!(gas_price, bump_gas_price(stuck, 15));
+        assert_eq!(free, bump_gas_price(current, 15));
+    }
+
+    #[tokio::test]
+    async fn test_send_reverted() {
+        // given
+        let anvil = &Anvil::new().spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+        // creation code of PUSH1 0 PUSH1 0 REVERT, with the gas set so that
+        // it isn't rejected by the gas estimation
+        let tx = TransactionRequest::new()
+            .data(hex::decode("60006000fd").unwrap())
+            .gas(100000u64);
+
+        // when
+        let result = manager.send(tx).await;
+
+        // then
+        assert!(result.unwrap_err().to_string().contains("reverted"));
+        assert_eq!(manager.next_nonce().await.unwrap(), 1.into());
+    }
+
+    #[tokio::test]
+    async fn test_wait_confirmations() {
+        // given
+        let anvil = &Anvil::new().block_time(1u64).spawn();
+        let client = get_provider(Some(anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager =
+            NonceManager::new(client, ReplaceOptions::default()).with_confirmations(2);
+        let address = manager.address();
+
+        // when
+        let tx = TransactionRequest::new().to(address).value(1u64);
+        let receipt = manager.send(tx).await.unwrap();
+
+        // then
+        let current = manager.client().get_block_number().await.unwrap();
+        assert!(current > receipt.block_number.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_wait_confirmations_over_ws() {
+        // given
+        let anvil = &Anvil::new().block_time(1u64).spawn();
+        let pri_key = hex::encode(anvil.keys()[0].to_be_bytes());
+        let network = Network {
+            rpc_url: Some(anvil.ws_endpoint()),
+            pri_key: Some(pri_key),
+            ..Default::default()
+        };
+        let client = get_provider(None, &network).await.unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default())
+            .with_confirmations(3)
+            .with_block_subscription();
+        let address = manager.address();
+
+        // when
+        let tx = TransactionRequest::new().to(address).value(1u64);
+        let receipt = manager.send(tx).await.unwrap();
+
+        // then
+        let current = manager.client().get_block_number().await.unwrap();
+        assert!(current >= receipt.block_number.unwrap() + 2);
+    }
+}
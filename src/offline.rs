@@ -0,0 +1,104 @@
+use ethers::{prelude::*, types::transaction::eip2718::TypedTransaction};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// signed raw transactions produced on an air-gapped machine, broadcast later
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedBatch {
+    pub chain_id: u64,
+    pub transactions: Vec<SignedTx>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedTx {
+    pub contract: String,
+    pub name: String,
+    pub nonce: u64,
+    pub hash: H256,
+    pub raw: Bytes,
+}
+
+impl SignedBatch {
+    pub fn new(chain_id: u64) -> Self {
+        SignedBatch {
+            chain_id,
+            transactions: vec![],
+        }
+    }
+
+    // sign the legacy transaction with the wallet and append the raw bytes
+    pub fn sign(
+        &mut self,
+        wallet: &LocalWallet,
+        contract: String,
+        name: String,
+        tx: TransactionRequest,
+    ) -> eyre::Result<&SignedTx> {
+        let nonce = tx
+            .nonce
+            .ok_or_else(|| eyre::eyre!("Nonce not set for contract {}", name))?;
+        let tx: TypedTransaction = tx.chain_id(self.chain_id).into();
+        let signature = wallet.sign_transaction_sync(&tx);
+
+        self.transactions.push(SignedTx {
+            contract,
+            name,
+            nonce: nonce.as_u64(),
+            hash: tx.hash(&signature),
+            raw: tx.rlp_signed(&signature),
+        });
+        Ok(self.transactions.last().unwrap())
+    }
+
+    pub fn save(&self, path: &str) -> eyre::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> eyre::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::keccak256;
+
+    #[test]
+    fn test_sign_batch() {
+        // given
+        let wallet: LocalWallet =
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+                .parse()
+                .unwrap();
+        let mut batch = SignedBatch::new(5);
+
+        // when
+        for nonce in 0..2u64 {
+            let tx = TransactionRequest::new()
+                .data(vec![0x60, 0x80, 0x60, 0x40])
+                .nonce(nonce)
+                .gas(3_000_000u64)
+                .gas_price(1_000_000_000u64);
+            batch
+                .sign(&wallet, "contract.sol".into(), "SimpleStorage".into(), tx)
+                .unwrap();
+        }
+
+        // then
+        assert_eq!(batch.transactions.len(), 2);
+        for (nonce, signed) in batch.transactions.iter().enumerate() {
+            assert_eq!(signed.nonce, nonce as u64);
+            assert_eq!(signed.hash, H256::from(keccak256(&signed.raw)));
+        }
+
+        let path = std::env::temp_dir().join("morge_signed_batch.json");
+        let path = path.to_str().unwrap();
+        batch.save(path).unwrap();
+        assert_eq!(SignedBatch::load(path).unwrap(), batch);
+        fs::remove_file(path).unwrap();
+    }
+}
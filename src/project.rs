@@ -0,0 +1,225 @@
+use once_cell::sync::OnceCell;
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{INIT_CFG, INIT_PATH};
+
+// config file given by --config, morge.toml of the project root if not set
+static CONFIG_FILE: OnceCell<PathBuf> = OnceCell::new();
+
+// the project root, the current directory once entered
+#[cfg(not(test))]
+pub fn root() -> std::io::Result<PathBuf> {
+    env::current_dir()
+}
+
+// a file of the project, as INIT_JOURNAL, in the project root
+#[cfg(not(test))]
+pub fn project_file(path: &str) -> PathBuf {
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+pub use test_root::{enter_test_root, project_file, root, TempRoot};
+
+// the nearest directory from dir upward holding morge.toml or .morge
+pub fn find_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(INIT_CFG).exists() || dir.join(INIT_PATH).is_dir())
+        .map(Path::to_path_buf)
+}
+
+// switch to the project root, the --root directory, the directory of the
+// --config file, or the nearest project root above the current directory if
+// discover is set. All the project files are relative to it. Return the
+// directory morge was run from, which paths given by the user are relative to
+pub fn enter(root: Option<&str>, config: Option<&str>, discover: bool) -> eyre::Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    let config = config.map(|config| cwd.join(config));
+    let root = match (root, &config) {
+        (Some(root), _) => cwd.join(root),
+        (None, Some(config)) => config
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| cwd.clone()),
+        (None, None) if discover => find_root(&cwd).unwrap_or_else(|| cwd.clone()),
+        (None, None) => cwd.clone(),
+    };
+    if !root.is_dir() {
+        return Err(eyre::eyre!("Project root {} not found", root.display()));
+    }
+    env::set_current_dir(&root)?;
+    if let Some(config) = config {
+        CONFIG_FILE
+            .set(config)
+            .map_err(|_| eyre::eyre!("Config file is already set"))?;
+    }
+    Ok(cwd)
+}
+
+pub fn config_file() -> PathBuf {
+    CONFIG_FILE
+        .get()
+        .cloned()
+        .unwrap_or_else(|| project_file(INIT_CFG))
+}
+
+// the path given from dir, relative to the project root when it is inside it
+// so that the config is the same on every machine, absolute otherwise
+pub fn relative_path(dir: &Path, path: &str) -> eyre::Result<String> {
+    let path = resolve(&dir.join(path));
+    let root = resolve(&root()?);
+    let path = path.strip_prefix(&root).unwrap_or(&path);
+    Ok(path.to_string_lossy().to_string())
+}
+
+// the real path if it exists, otherwise with . and .. removed
+fn resolve(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        path.components()
+            .fold(PathBuf::new(), |mut path, component| {
+                match component {
+                    Component::CurDir => {}
+                    Component::ParentDir => {
+                        path.pop();
+                    }
+                    component => path.push(component),
+                }
+                path
+            })
+    })
+}
+
+// the tests run in parallel on the threads of one process, so each one uses a
+// project root of its own instead of the current directory
+#[cfg(test)]
+mod test_root {
+    use std::cell::RefCell;
+    use std::env;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        // project root of the test running on this thread
+        static TEST_ROOT: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    }
+
+    fn test_root() -> Option<PathBuf> {
+        TEST_ROOT.with(|root| root.borrow().clone())
+    }
+
+    pub fn root() -> io::Result<PathBuf> {
+        match test_root() {
+            Some(root) => Ok(root),
+            None => env::current_dir(),
+        }
+    }
+
+    pub fn project_file(path: &str) -> PathBuf {
+        match test_root() {
+            Some(root) => root.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    // use the project root on the current thread, for the threads a test spawns
+    pub fn enter_test_root(path: &Path) {
+        TEST_ROOT.with(|root| *root.borrow_mut() = Some(path.to_path_buf()));
+    }
+
+    // a project root of its own for a test, used by the thread creating it
+    // and removed when dropped
+    pub struct TempRoot {
+        pub path: PathBuf,
+    }
+
+    impl TempRoot {
+        pub fn new() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = env::temp_dir().join(format!(
+                "morge-test-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            std::fs::create_dir_all(&path).unwrap();
+            enter_test_root(&path);
+            TempRoot { path }
+        }
+    }
+
+    impl Default for TempRoot {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            TEST_ROOT.with(|root| *root.borrow_mut() = None);
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_root() {
+        // given
+        let root = TempRoot::new();
+        let project = root.path.join("project");
+        let nested = project.join("contracts/tokens");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(project.join(INIT_CFG), "version = 1\n").unwrap();
+
+        // when
+        let found = find_root(&nested);
+
+        // then
+        assert_eq!(found, Some(project));
+        assert_eq!(find_root(Path::new("/")), None);
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let root = env::current_dir().unwrap();
+        assert_eq!(project_file(INIT_CFG), Path::new(INIT_CFG));
+        assert_eq!(
+            relative_path(&root.join("examples"), "contract.sol").unwrap(),
+            "examples/contract.sol"
+        );
+        assert_eq!(
+            relative_path(&root, "../outside.sol").unwrap(),
+            root.parent()
+                .unwrap()
+                .join("outside.sol")
+                .to_string_lossy()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_temp_root() {
+        // given
+        let cwd = env::current_dir().unwrap();
+        let root = TempRoot::new();
+
+        // then the project files are in the temp root
+        assert_eq!(config_file(), root.path.join(INIT_CFG));
+        assert_eq!(
+            relative_path(&cwd, "examples/contract.sol").unwrap(),
+            cwd.join("examples/contract.sol").to_string_lossy()
+        );
+
+        // when
+        let path = root.path.clone();
+        drop(root);
+
+        // then
+        assert!(!path.exists());
+        assert_eq!(config_file(), Path::new(INIT_CFG));
+    }
+}
@@ -0,0 +1,314 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::time::Duration;
+
+pub static DEFAULT_MAX_RETRIES: u32 = 5;
+static INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+static MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// errors which will fail again however many times the request is sent
+static FATAL_ERRORS: [&str; 9] = [
+    "revert",
+    "nonce too low",
+    "nonce too high",
+    "invalid nonce",
+    "insufficient funds",
+    "underpriced",
+    "already known",
+    "intrinsic gas too low",
+    "exceeds block gas limit",
+];
+
+// rate limits, timeouts and connection failures
+static RETRYABLE_ERRORS: [&str; 14] = [
+    "429",
+    "too many requests",
+    "rate limit",
+    "limit exceeded",
+    "timeout",
+    "timed out",
+    "connection reset",
+    "connection refused",
+    "connection closed",
+    "error sending request",
+    "502",
+    "503",
+    "504",
+    "temporarily unavailable",
+];
+
+// errors of a resent transaction which the node already has from the first send
+static KNOWN_TX_ERRORS: [&str; 2] = ["already known", "known transaction"];
+
+pub fn is_retryable(err: &str) -> bool {
+    let err = err.to_lowercase();
+    if FATAL_ERRORS.iter().any(|fatal| err.contains(fatal)) {
+        return false;
+    }
+    RETRYABLE_ERRORS
+        .iter()
+        .any(|retryable| err.contains(retryable))
+}
+
+// exponential backoff from the initial backoff, capped by the max backoff
+pub fn backoff(initial: Duration, attempt: u32) -> Duration {
+    initial
+        .checked_mul(2u32.saturating_pow(attempt))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+// retry the request while it fails with a retryable error
+pub async fn retry<T, E, F, Fut>(max_retries: u32, mut request: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Err(err) if attempt < max_retries && is_retryable(&err.to_string()) => {
+                let wait = backoff(INITIAL_BACKOFF, attempt);
+                attempt += 1;
+                println!(
+                    "Request failed: {}, retry {}/{} in {}ms",
+                    err,
+                    attempt,
+                    max_retries,
+                    wait.as_millis()
+                );
+                tokio::time::sleep(wait).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+// transport which retries the requests of the inner transport with exponential backoff
+#[derive(Debug)]
+pub struct RetryTransport<T> {
+    inner: T,
+    max_retries: u32,
+    initial_backoff: Duration,
+}
+
+impl<T: JsonRpcClient> RetryTransport<T> {
+    pub fn new(inner: T, max_retries: u32) -> Self {
+        RetryTransport {
+            inner,
+            max_retries,
+            initial_backoff: INITIAL_BACKOFF,
+        }
+    }
+
+    pub fn with_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+}
+
+#[async_trait]
+impl<T: JsonRpcClient> JsonRpcClient for RetryTransport<T> {
+    type Error = ProviderError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        // serialize once so that the params can be sent again
+        let params = serde_json::to_value(params)?;
+        let mut attempt = 0;
+        loop {
+            let err: ProviderError = match self.inner.request(method, &params).await {
+                Ok(res) => return Ok(res),
+                Err(err) => err.into(),
+            };
+            // the first send may have reached the node before it timed out, the
+            // transaction is then pending under the hash of the raw transaction
+            if attempt > 0 && method == "eth_sendRawTransaction" {
+                let known = err.to_string().to_lowercase();
+                if KNOWN_TX_ERRORS.iter().any(|error| known.contains(error)) {
+                    if let Some(hash) = raw_tx_hash(&params) {
+                        return Ok(serde_json::from_value(serde_json::to_value(hash)?)?);
+                    }
+                }
+            }
+            // the node signs and may have sent it, so it can't be sent again
+            if method == "eth_sendTransaction"
+                || attempt >= self.max_retries
+                || !is_retryable(&err.to_string())
+            {
+                return Err(err);
+            }
+
+            let wait = backoff(self.initial_backoff, attempt);
+            attempt += 1;
+            println!(
+                "Rpc request {} failed: {}, retry {}/{} in {}ms",
+                method,
+                err,
+                attempt,
+                self.max_retries,
+                wait.as_millis()
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// hash of the raw transaction sent by eth_sendRawTransaction
+fn raw_tx_hash(params: &serde_json::Value) -> Option<H256> {
+    let raw = serde_json::from_value::<Bytes>(params.get(0)?.clone()).ok()?;
+    Some(H256::from(ethers::utils::keccak256(&raw)))
+}
+
+// subscriptions are not retried, the stream is handed over to the caller
+impl<T: PubsubClient> PubsubClient for RetryTransport<T> {
+    type NotificationStream = T::NotificationStream;
+
+    fn subscribe<I: Into<U256>>(&self, id: I) -> Result<Self::NotificationStream, Self::Error> {
+        self.inner.subscribe(id).map_err(Into::into)
+    }
+
+    fn unsubscribe<I: Into<U256>>(&self, id: I) -> Result<(), Self::Error> {
+        self.inner.unsubscribe(id).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // fails the first requests with the given error
+    #[derive(Debug)]
+    struct FlakyTransport {
+        failures: u32,
+        error: &'static str,
+        requests: AtomicU32,
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for FlakyTransport {
+        type Error = ProviderError;
+
+        async fn request<P, R>(&self, _method: &str, _params: P) -> Result<R, Self::Error>
+        where
+            P: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            if self.requests.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(ProviderError::CustomError(self.error.to_string()));
+            }
+            Ok(serde_json::from_value(serde_json::json!("0x1"))?)
+        }
+    }
+
+    fn flaky_provider(
+        failures: u32,
+        error: &'static str,
+    ) -> Provider<RetryTransport<FlakyTransport>> {
+        let transport = FlakyTransport {
+            failures,
+            error,
+            requests: AtomicU32::new(0),
+        };
+        Provider::new(RetryTransport::new(transport, 3).with_backoff(Duration::from_millis(10)))
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(
+            "(code: 429, message: Too Many Requests, data: None)"
+        ));
+        assert!(is_retryable(
+            "error sending request: connection reset by peer"
+        ));
+        assert!(is_retryable("502 Bad Gateway"));
+        assert!(!is_retryable(
+            "(code: 3, message: execution reverted, data: None)"
+        ));
+        assert!(!is_retryable("nonce too low"));
+        assert!(!is_retryable("invalid params"));
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(
+            backoff(Duration::from_millis(500), 0),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            backoff(Duration::from_millis(500), 2),
+            Duration::from_secs(2)
+        );
+        assert_eq!(backoff(Duration::from_millis(500), 40), MAX_BACKOFF);
+    }
+
+    // times out the first send after the node accepted the transaction, which
+    // is then known by the node
+    #[derive(Debug)]
+    struct TimeoutOnSend {
+        sends: AtomicU32,
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for TimeoutOnSend {
+        type Error = ProviderError;
+
+        async fn request<P, R>(&self, method: &str, _params: P) -> Result<R, Self::Error>
+        where
+            P: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            assert_eq!(method, "eth_sendRawTransaction");
+            match self.sends.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(ProviderError::CustomError("request timed out".into())),
+                _ => Err(ProviderError::CustomError(
+                    "(code: -32000, message: already known, data: None)".into(),
+                )),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resend_already_known() {
+        // given
+        let transport = TimeoutOnSend {
+            sends: AtomicU32::new(0),
+        };
+        let provider = Provider::new(
+            RetryTransport::new(transport, 3).with_backoff(Duration::from_millis(10)),
+        );
+        let raw = Bytes::from(vec![0xf8, 0x6b, 0x80, 0x01]);
+
+        // when
+        let pending = provider.send_raw_transaction(raw.clone()).await.unwrap();
+
+        // then
+        assert_eq!(*pending, H256::from(ethers::utils::keccak256(&raw)));
+        assert_eq!(provider.as_ref().inner.sends.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_transport() {
+        // retryable errors are retried until success
+        let provider = flaky_provider(2, "429 Too Many Requests");
+        assert_eq!(provider.get_chainid().await.unwrap(), 1.into());
+
+        // give up after the max retries
+        let provider = flaky_provider(4, "request timed out");
+        assert!(provider.get_chainid().await.is_err());
+        assert_eq!(provider.as_ref().inner.requests.load(Ordering::SeqCst), 4);
+
+        // fatal errors are not retried
+        let provider = flaky_provider(1, "execution reverted");
+        assert!(provider.get_chainid().await.is_err());
+        assert_eq!(provider.as_ref().inner.requests.load(Ordering::SeqCst), 1);
+    }
+}
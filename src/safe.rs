@@ -0,0 +1,167 @@
+use chrono::Local;
+use ethers::{
+    abi::{self, Token},
+    prelude::*,
+    utils::{get_create2_address, hex, id},
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// Safe CreateCall helper, deployed at the same address on every supported chain
+pub static CREATE_CALL: &str = "0x7cbB62EaA69F79e6873cD1ecB2392971036cFAa4";
+static TX_BUILDER_VERSION: &str = "1.10.0";
+
+// batch file which can be imported by the Safe Transaction Builder app
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeBatch {
+    pub version: String,
+    pub chain_id: String,
+    pub created_at: i64,
+    pub meta: SafeMeta,
+    pub transactions: Vec<SafeTransaction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeMeta {
+    pub name: String,
+    pub description: String,
+    pub tx_builder_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_from_safe_address: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeTransaction {
+    pub to: String,
+    pub value: String,
+    pub data: String,
+    pub contract_method: Option<serde_json::Value>,
+    pub contract_inputs_values: Option<serde_json::Value>,
+}
+
+impl SafeBatch {
+    pub fn new(chain_id: u64, safe_address: Option<String>) -> Self {
+        SafeBatch {
+            version: "1.0".to_string(),
+            chain_id: chain_id.to_string(),
+            created_at: Local::now().timestamp_millis(),
+            meta: SafeMeta {
+                name: "morge deployment".to_string(),
+                description: "Contracts deployment batch exported by morge".to_string(),
+                tx_builder_version: TX_BUILDER_VERSION.to_string(),
+                created_from_safe_address: safe_address,
+            },
+            transactions: vec![],
+        }
+    }
+
+    // add a contract deployment through the CreateCall helper, return the
+    // predicted contract address when deployed with CREATE2. The helper is
+    // called, not delegatecalled, so it is the deployer and msg.sender of the
+    // constructor instead of the Safe, contracts taking their owner from
+    // msg.sender must take it as a constructor arg
+    pub fn add_deployment(
+        &mut self,
+        deployment_data: Bytes,
+        salt: Option<H256>,
+    ) -> Option<Address> {
+        let create_call = CREATE_CALL.parse::<Address>().unwrap();
+        let (data, address) = match salt {
+            Some(salt) => (
+                encode_call(
+                    "performCreate2(uint256,bytes,bytes32)",
+                    &[
+                        Token::Uint(U256::zero()),
+                        Token::Bytes(deployment_data.to_vec()),
+                        Token::FixedBytes(salt.as_bytes().to_vec()),
+                    ],
+                ),
+                Some(get_create2_address(
+                    create_call,
+                    salt.as_bytes().to_vec(),
+                    deployment_data,
+                )),
+            ),
+            None => (
+                encode_call(
+                    "performCreate(uint256,bytes)",
+                    &[
+                        Token::Uint(U256::zero()),
+                        Token::Bytes(deployment_data.to_vec()),
+                    ],
+                ),
+                None,
+            ),
+        };
+
+        self.transactions.push(SafeTransaction {
+            to: format!("{:?}", create_call),
+            value: "0".to_string(),
+            data: format!("0x{}", hex::encode(data)),
+            contract_method: None,
+            contract_inputs_values: None,
+        });
+        address
+    }
+
+    // add a call made by the Safe, as a call of a deployed contract
+    pub fn add_call(&mut self, to: Address, data: Bytes) {
+        self.transactions.push(SafeTransaction {
+            to: format!("{:?}", to),
+            value: "0".to_string(),
+            data: format!("0x{}", hex::encode(data)),
+            contract_method: None,
+            contract_inputs_values: None,
+        });
+    }
+
+    pub fn save(&self, path: &str) -> eyre::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+fn encode_call(signature: &str, args: &[Token]) -> Vec<u8> {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_deployment() {
+        // given
+        let mut batch = SafeBatch::new(5, None);
+        let deployment_data = Bytes::from(vec![0x60, 0x80, 0x60, 0x40]);
+
+        // when
+        let create = batch.add_deployment(deployment_data.clone(), None);
+        let create2 = batch.add_deployment(deployment_data, Some(H256::zero()));
+
+        // then
+        assert!(create.is_none());
+        assert!(create2.is_some());
+        assert_eq!(batch.chain_id, "5");
+        assert_eq!(batch.transactions.len(), 2);
+        assert!(batch.transactions[0].data.starts_with("0x4c8c9ea1"));
+        assert!(batch.transactions[1].data.starts_with("0x4847be6f"));
+        assert_eq!(batch.transactions[0].to, CREATE_CALL.to_lowercase());
+
+        // a call of the deployed contract follows its deployment
+        batch.add_call(create2.unwrap(), Bytes::from(vec![0x20, 0x96, 0x52, 0x55]));
+        assert_eq!(batch.transactions.len(), 3);
+        assert_eq!(batch.transactions[2].to, format!("{:?}", create2.unwrap()));
+        assert_eq!(batch.transactions[2].data, "0x20965255");
+
+        let json = serde_json::to_value(&batch).unwrap();
+        assert!(json["meta"]["txBuilderVersion"].is_string());
+        assert!(json["transactions"][0]["contractMethod"].is_null());
+    }
+}
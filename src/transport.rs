@@ -0,0 +1,343 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use futures::future::join_all;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::retry::{is_retryable, RetryTransport};
+
+// reads which must be agreed by the quorum of rpc urls, nonces only of mined
+// transactions since the pending ones differ from node to node
+static QUORUM_METHODS: [&str; 4] = [
+    "eth_chainId",
+    "eth_getTransactionCount",
+    "eth_getTransactionReceipt",
+    "eth_getCode",
+];
+
+// rpc transport chosen from the scheme of the rpc url, or a failover between
+// several rpc urls
+#[derive(Debug)]
+pub enum Transport {
+    Http(RetryTransport<Http>),
+    Ws(RetryTransport<Ws>),
+    Ipc(RetryTransport<Ipc>),
+    Failover(FailoverTransport),
+}
+
+// sends requests to the active rpc url and fails over to the next one when it
+// is down, reads of the quorum methods are sent to all of them
+#[derive(Debug)]
+pub struct FailoverTransport {
+    transports: Vec<Transport>,
+    quorum: usize,
+    active: AtomicUsize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheme {
+    Http,
+    Ws,
+    Ipc,
+}
+
+impl Scheme {
+    // ws:// and wss:// are websockets, ipc:// or a socket file path is ipc
+    pub fn from_url(url: &str) -> Self {
+        if url.starts_with("ws://") || url.starts_with("wss://") {
+            Scheme::Ws
+        } else if url.starts_with("ipc://") || url.ends_with(".ipc") || url.starts_with('/') {
+            Scheme::Ipc
+        } else {
+            Scheme::Http
+        }
+    }
+}
+
+impl Transport {
+    pub async fn connect(url: &str, max_retries: u32) -> eyre::Result<Self> {
+        let transport = match Scheme::from_url(url) {
+            Scheme::Http => Transport::Http(RetryTransport::new(Http::from_str(url)?, max_retries)),
+            Scheme::Ws => Transport::Ws(RetryTransport::new(Ws::connect(url).await?, max_retries)),
+            Scheme::Ipc => {
+                let path = url.strip_prefix("ipc://").unwrap_or(url);
+                Transport::Ipc(RetryTransport::new(Ipc::connect(path).await?, max_retries))
+            }
+        };
+        Ok(transport)
+    }
+
+    // a single transport for one rpc url, a failover transport for several
+    pub async fn connect_all(
+        rpc_urls: &[String],
+        max_retries: u32,
+        quorum: usize,
+    ) -> eyre::Result<Self> {
+        if rpc_urls.is_empty() {
+            return Err(eyre::eyre!("Rpc url not set, please set"));
+        }
+        if quorum > rpc_urls.len() {
+            return Err(eyre::eyre!(
+                "Quorum {} is larger than the {} rpc urls",
+                quorum,
+                rpc_urls.len()
+            ));
+        }
+        if rpc_urls.len() == 1 {
+            return Transport::connect(&rpc_urls[0], max_retries).await;
+        }
+
+        let mut transports = vec![];
+        for rpc_url in rpc_urls.iter() {
+            transports.push(Transport::connect(rpc_url, max_retries).await?);
+        }
+        Ok(Transport::Failover(FailoverTransport {
+            transports,
+            quorum,
+            active: AtomicUsize::new(0),
+        }))
+    }
+}
+
+impl FailoverTransport {
+    fn active(&self) -> &Transport {
+        &self.transports[self.active.load(Ordering::SeqCst)]
+    }
+
+    async fn failover_request(&self, method: &str, params: &Value) -> Result<Value, ProviderError> {
+        let active = self.active.load(Ordering::SeqCst);
+        let mut last_err = None;
+        for i in 0..self.transports.len() {
+            let idx = (active + i) % self.transports.len();
+            match self.transports[idx].request(method, params).await {
+                Ok(res) => {
+                    if idx != active {
+                        println!("Fail over to rpc url #{}", idx);
+                        self.active.store(idx, Ordering::SeqCst);
+                    }
+                    return Ok(res);
+                }
+                // the node is down or rate limited, try the next one
+                Err(err) if is_retryable(&err.to_string()) => {
+                    println!("Rpc url #{} request {} failed: {}", idx, method, err);
+                    last_err = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    async fn quorum_request(&self, method: &str, params: &Value) -> Result<Value, ProviderError> {
+        let responses = join_all(
+            self.transports
+                .iter()
+                .map(|transport| transport.request::<_, Value>(method, params)),
+        )
+        .await;
+
+        let mut votes: Vec<(Value, usize)> = vec![];
+        // a receipt not found by a node lagging a block behind is no vote
+        for response in responses.into_iter().flatten().filter(|res| !res.is_null()) {
+            match votes.iter_mut().find(|(value, _)| *value == response) {
+                Some((_, count)) => *count += 1,
+                None => votes.push((response, 1)),
+            }
+        }
+        let agreed = votes
+            .into_iter()
+            .find(|(_, count)| *count >= self.quorum)
+            .map(|(value, _)| value);
+        match agreed {
+            Some(value) => Ok(value),
+            // not mined on enough of the nodes yet
+            None if method == "eth_getTransactionReceipt" => Ok(Value::Null),
+            None => Err(ProviderError::CustomError(format!(
+                "Quorum of {} rpc urls not reached for {}",
+                self.quorum, method
+            ))),
+        }
+    }
+}
+
+// nonces counting the pending transactions of the node
+fn is_pending_nonce(method: &str, params: &Value) -> bool {
+    method == "eth_getTransactionCount" && params.get(1).and_then(Value::as_str) == Some("pending")
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverTransport {
+    type Error = ProviderError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params)?;
+        let quorum = self.quorum > 1
+            && QUORUM_METHODS.contains(&method)
+            && !is_pending_nonce(method, &params);
+        let res = match quorum {
+            true => self.quorum_request(method, &params).await?,
+            false => self.failover_request(method, &params).await?,
+        };
+        Ok(serde_json::from_value(res)?)
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for Transport {
+    type Error = ProviderError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        match self {
+            Transport::Http(http) => http.request(method, params).await,
+            Transport::Ws(ws) => ws.request(method, params).await,
+            Transport::Ipc(ipc) => ipc.request(method, params).await,
+            Transport::Failover(failover) => failover.request(method, params).await,
+        }
+    }
+}
+
+// http doesn't support subscriptions, callers fall back to polling. Failover
+// subscribes through the active rpc url
+impl PubsubClient for Transport {
+    type NotificationStream = <Ws as PubsubClient>::NotificationStream;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, Self::Error> {
+        match self {
+            Transport::Http(_) => Err(ProviderError::UnsupportedRPC),
+            Transport::Ws(ws) => ws.subscribe(id),
+            Transport::Ipc(ipc) => ipc.subscribe(id),
+            Transport::Failover(failover) => failover.active().subscribe(id),
+        }
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), Self::Error> {
+        match self {
+            Transport::Http(_) => Err(ProviderError::UnsupportedRPC),
+            Transport::Ws(ws) => ws.unsubscribe(id),
+            Transport::Ipc(ipc) => ipc.unsubscribe(id),
+            Transport::Failover(failover) => failover.active().unsubscribe(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Network;
+    use crate::utils::parse::{get_provider, get_rpc_provider};
+    use ethers::utils::Anvil;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_scheme_from_url() {
+        assert_eq!(Scheme::from_url("http://localhost:8545"), Scheme::Http);
+        assert_eq!(Scheme::from_url("https://rpc.ankr.com/eth"), Scheme::Http);
+        assert_eq!(Scheme::from_url("ws://localhost:8546"), Scheme::Ws);
+        assert_eq!(Scheme::from_url("wss://rpc.ankr.com/eth/ws"), Scheme::Ws);
+        assert_eq!(Scheme::from_url("ipc:///tmp/geth.ipc"), Scheme::Ipc);
+        assert_eq!(Scheme::from_url("/tmp/anvil.ipc"), Scheme::Ipc);
+    }
+
+    #[tokio::test]
+    async fn test_ws_subscribe_blocks() {
+        // given
+        let anvil = Anvil::new().block_time(1u64).spawn();
+        let provider = get_rpc_provider(&[anvil.ws_endpoint()], 0, 0)
+            .await
+            .unwrap();
+
+        // when
+        let mut blocks = provider.subscribe_blocks().await.unwrap();
+
+        // then
+        assert!(blocks.next().await.is_some());
+        assert_eq!(provider.get_chainid().await.unwrap(), 31337.into());
+    }
+
+    #[tokio::test]
+    async fn test_http_subscribe_unsupported() {
+        let anvil = Anvil::new().spawn();
+        let provider = get_rpc_provider(&[anvil.endpoint()], 0, 0).await.unwrap();
+        assert!(provider.subscribe_blocks().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        // given the first rpc url is down
+        let anvil = Anvil::new().spawn();
+        let rpc_urls = vec!["http://127.0.0.1:1".to_string(), anvil.endpoint()];
+        let provider = get_rpc_provider(&rpc_urls, 0, 0).await.unwrap();
+
+        // when
+        let chain_id = provider.get_chainid().await.unwrap();
+        let block_number = provider.get_block_number().await.unwrap();
+
+        // then
+        assert_eq!(chain_id, 31337.into());
+        assert_eq!(block_number, 0.into());
+    }
+
+    #[tokio::test]
+    async fn test_quorum() {
+        // given
+        let anvil = Anvil::new().spawn();
+        let other = Anvil::new().args(vec!["--chain-id", "1337"]).spawn();
+
+        // when the rpc urls agree
+        let rpc_urls = vec![anvil.endpoint(), anvil.endpoint()];
+        let provider = get_rpc_provider(&rpc_urls, 0, 2).await.unwrap();
+
+        // then
+        assert_eq!(provider.get_chainid().await.unwrap(), 31337.into());
+
+        // when the rpc urls disagree
+        let rpc_urls = vec![anvil.endpoint(), other.endpoint()];
+        let provider = get_rpc_provider(&rpc_urls, 0, 2).await.unwrap();
+
+        // then
+        assert!(provider.get_chainid().await.is_err());
+        assert!(get_rpc_provider(&rpc_urls, 0, 3).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_quorum_on_lagging_node() {
+        // given a transaction mined on one node only
+        let anvil = Anvil::new().spawn();
+        let lagging = Anvil::new().spawn();
+        let client = get_provider(Some(&anvil), &Network::default())
+            .await
+            .unwrap();
+        let address = client.address();
+        let tx = TransactionRequest::new().to(address).value(1u64);
+        let receipt = client
+            .send_transaction(tx, None)
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let hash = receipt.unwrap().transaction_hash;
+        let rpc_urls = vec![anvil.endpoint(), lagging.endpoint()];
+        let provider = get_rpc_provider(&rpc_urls, 0, 2).await.unwrap();
+
+        // when
+        let found = provider.get_transaction_receipt(hash).await;
+        let nonce = provider
+            .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+            .await;
+
+        // then the receipt is not found yet instead of failing
+        assert!(found.unwrap().is_none());
+        assert_eq!(nonce.unwrap(), 1.into());
+    }
+}
@@ -1,10 +1,67 @@
+use fs2::FileExt;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
 use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::project::project_file;
+use crate::INIT_PATH;
 
 pub fn is_existed(path: &String) -> bool {
     Path::new(path).exists()
 }
 
+// create the .morge directory, ignored by git as a whole since it holds the
+// secrets and the generated files
+pub fn create_project_dir() -> io::Result<()> {
+    let dir = project_file(INIT_PATH);
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(gitignore, "# generated by morge\n*\n")?;
+    }
+    Ok(())
+}
+
+// take the exclusive lock of the file in .morge, blocking until the other
+// morge processes release it, the lock is released when the file is dropped
+pub fn lock_exclusive(path: &Path) -> io::Result<File> {
+    create_project_dir()?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    file.lock_exclusive()?;
+    Ok(file)
+}
+
 pub fn is_contract_existed(contract: String) -> bool {
     let contract_vec = contract.split(":").collect::<Vec<&str>>();
     is_existed(&contract_vec[0].into())
 }
+
+// write to a temporary file beside the path then rename it over the path, so
+// the file is never seen partly written, even if morge is killed mid-write
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    let result = fs::write(&tmp, contents).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
@@ -1,7 +1,8 @@
 use ethers::prelude::k256::ecdsa::SigningKey;
+use ethers::utils::parse_units;
 use ethers::utils::AnvilInstance;
 use ethers::{
-    abi::Constructor,
+    abi::{Constructor, Function, Param},
     core::{
         abi::{
             token::{LenientTokenizer, StrictTokenizer, Tokenizer},
@@ -12,9 +13,15 @@ use ethers::{
     prelude::*,
 };
 use eyre::{eyre, Result, WrapErr};
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
 use std::time::Duration;
 
+use crate::config::Network;
+use crate::project::relative_path;
+use crate::transport::Transport;
+
 // copied from https://github.com/foundry-rs/foundry
 /// Parses string input as Token against the expected ParamType
 // #[allow(clippy::no_effect)]
@@ -33,15 +40,13 @@ pub fn parse_tokens<'a, I: IntoIterator<Item = (&'a ParamType, &'a str)>>(
 
             if token.is_err() && value.starts_with("0x") {
                 match param {
-                    ParamType::FixedBytes(32) => {
-                        if value.len() < 66 {
-                            let padded_value = [value, &"0".repeat(66 - value.len())].concat();
-                            token = if lenient {
-                                LenientTokenizer::tokenize(param, &padded_value)
-                            } else {
-                                StrictTokenizer::tokenize(param, &padded_value)
-                            };
-                        }
+                    ParamType::FixedBytes(32) if value.len() < 66 => {
+                        let padded_value = [value, &"0".repeat(66 - value.len())].concat();
+                        token = if lenient {
+                            LenientTokenizer::tokenize(param, &padded_value)
+                        } else {
+                            StrictTokenizer::tokenize(param, &padded_value)
+                        };
                     }
                     ParamType::Uint(_) => {
                         // try again if value is hex
@@ -64,18 +69,402 @@ pub fn parse_tokens<'a, I: IntoIterator<Item = (&'a ParamType, &'a str)>>(
         .wrap_err("Failed to parse tokens")
 }
 
+// the args must match the constructor inputs, one by one or keyed by the
+// input names, a contract without constructor takes no args
 pub fn parse_constructor_args(
-    constructor: &Constructor,
+    constructor: Option<&Constructor>,
     constructor_args: &[String],
 ) -> Result<Vec<Token>> {
-    let params = constructor
-        .inputs
+    constructor_tokens(constructor, constructor_args)
+        .map_err(|problems| eyre!("Invalid constructor args:\n  {}", problems.join("\n  ")))
+}
+
+// the problems of the args against the constructor, with the expected
+// signature when the count differs and the expected format of each arg which
+// doesn't parse as its type
+pub fn check_constructor_args(constructor: Option<&Constructor>, args: &[String]) -> Vec<String> {
+    constructor_tokens(constructor, args)
+        .err()
+        .unwrap_or_default()
+}
+
+fn constructor_tokens(
+    constructor: Option<&Constructor>,
+    args: &[String],
+) -> std::result::Result<Vec<Token>, Vec<String>> {
+    let inputs = constructor
+        .map(|constructor| constructor.inputs.as_slice())
+        .unwrap_or_default();
+    let args = named_args(inputs, args)?.unwrap_or_else(|| args.to_vec());
+    let mut problems = vec![];
+    if args.len() != inputs.len() {
+        problems.push(match inputs.len() {
+            0 => format!(
+                "expected no constructor args, the contract has no constructor inputs, got {}",
+                args.len()
+            ),
+            len => format!(
+                "expected {} constructor args constructor({}), got {}",
+                len,
+                input_signature(inputs),
+                args.len()
+            ),
+        });
+    }
+    arg_tokens(inputs, &args, problems)
+}
+
+// the args of a call of the function, given as the constructor args
+pub fn parse_function_args(function: &Function, args: &[String]) -> Result<Vec<Token>> {
+    let inputs = function.inputs.as_slice();
+    let tokens = named_args(inputs, args).and_then(|named| {
+        let args = named.unwrap_or_else(|| args.to_vec());
+        let mut problems = vec![];
+        if args.len() != inputs.len() {
+            problems.push(format!(
+                "expected {} args {}({}), got {}",
+                inputs.len(),
+                function.name,
+                input_signature(inputs),
+                args.len()
+            ));
+        }
+        arg_tokens(inputs, &args, problems)
+    });
+    tokens.map_err(|problems| {
+        eyre!(
+            "Invalid args of {}:\n  {}",
+            function.name,
+            problems.join("\n  ")
+        )
+    })
+}
+
+fn input_signature(inputs: &[Param]) -> String {
+    inputs
+        .iter()
+        .map(|input| format!("{} {}", input.kind, input.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// the tokens of the args as the inputs, adding the expected format of each
+// arg which doesn't parse as its type to the problems
+fn arg_tokens(
+    inputs: &[Param],
+    args: &[String],
+    mut problems: Vec<String>,
+) -> std::result::Result<Vec<Token>, Vec<String>> {
+    let mut tokens = vec![];
+    for (idx, (input, arg)) in inputs.iter().zip(args.iter()).enumerate() {
+        match parse_arg(&input.kind, arg) {
+            Ok(token) => tokens.push(token),
+            Err(err) if unquote(arg).starts_with(FILE_PREFIX) => {
+                problems.push(format!("arg {} {:?} for {}: {}", idx, arg, input.name, err))
+            }
+            Err(_) => problems.push(format!(
+                "arg {} {:?} is not a valid {} for {}, expected {}",
+                idx,
+                arg,
+                input.kind,
+                input.name,
+                param_format(&input.kind)
+            )),
+        }
+    }
+    match problems.is_empty() {
+        true => Ok(tokens),
+        false => Err(problems),
+    }
+}
+
+// args given as name=value for every input, in the order of the inputs. None
+// if none is named by the inputs, they are positional then
+fn named_args(
+    inputs: &[Param],
+    args: &[String],
+) -> std::result::Result<Option<Vec<String>>, Vec<String>> {
+    let named = args
         .iter()
-        .zip(constructor_args)
-        .map(|(input, arg)| (&input.kind, arg.as_str()))
+        .filter_map(|arg| {
+            unquote(arg)
+                .split_once('=')
+                .filter(|(name, _)| inputs.iter().any(|input| input.name == name.trim()))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        })
         .collect::<Vec<_>>();
+    if named.is_empty() {
+        return Ok(None);
+    }
+    if named.len() != args.len() {
+        return Err(vec![format!(
+            "mix of named and positional args, {} of {} are named",
+            named.len(),
+            args.len()
+        )]);
+    }
+
+    let mut problems = vec![];
+    let mut ordered = vec![];
+    for input in inputs {
+        let values = named
+            .iter()
+            .filter(|(name, _)| *name == input.name)
+            .map(|(_, value)| value.clone())
+            .collect::<Vec<_>>();
+        match values.len() {
+            0 => problems.push(format!("missing arg {} {}", input.kind, input.name)),
+            1 => ordered.extend(values),
+            _ => problems.push(format!("arg {} given more than once", input.name)),
+        }
+    }
+    match problems.is_empty() {
+        true => Ok(Some(ordered)),
+        false => Err(problems),
+    }
+}
+
+static FILE_PREFIX: &str = "@file:";
+
+// the token of the arg as the type. Besides the plain values, arrays are
+// written as [a, b], tuples as (a, b), both nesting, uints as amounts with a
+// wei, gwei or ether unit, and @file:path reads bytes, or the json value of
+// any other type, from the file
+pub fn parse_arg(param: &ParamType, value: &str) -> Result<Token> {
+    let value = unquote(value.trim());
+    if let Some(path) = value.strip_prefix(FILE_PREFIX) {
+        return file_arg(param, path.trim());
+    }
+    match param {
+        ParamType::Array(inner) => Ok(Token::Array(
+            split_items(value, '[', ']')?
+                .iter()
+                .map(|item| parse_arg(inner, item))
+                .collect::<Result<_>>()?,
+        )),
+        ParamType::FixedArray(inner, len) => {
+            let items = split_items(value, '[', ']')?;
+            if items.len() != *len {
+                return Err(eyre!("expected {} items, got {}", len, items.len()));
+            }
+            Ok(Token::FixedArray(
+                items
+                    .iter()
+                    .map(|item| parse_arg(inner, item))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        ParamType::Tuple(params) => {
+            let items = split_items(value, '(', ')')?;
+            if items.len() != params.len() {
+                return Err(eyre!(
+                    "expected {} tuple items, got {}",
+                    params.len(),
+                    items.len()
+                ));
+            }
+            Ok(Token::Tuple(
+                params
+                    .iter()
+                    .zip(items.iter())
+                    .map(|(param, item)| parse_arg(param, item))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        ParamType::Uint(_) => match parse_amount(value)? {
+            Some(amount) => Ok(Token::Uint(amount)),
+            None => single_token(param, value),
+        },
+        _ => single_token(param, value),
+    }
+}
+
+fn single_token(param: &ParamType, value: &str) -> Result<Token> {
+    parse_tokens([(param, value)], true)?
+        .pop()
+        .ok_or_else(|| eyre!("Failed to parse tokens"))
+}
+
+// the wei of an amount as 1.5 ether, 20 gwei or 100 wei, None if the value has
+// no unit
+fn parse_amount(value: &str) -> Result<Option<U256>> {
+    if value.starts_with("0x") {
+        return Ok(None);
+    }
+    let unit_start = match value.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(unit_start) => unit_start,
+        None => return Ok(None),
+    };
+    let (amount, unit) = value.split_at(unit_start);
+    let unit = unit.trim().to_lowercase();
+    if !["wei", "gwei", "ether"].contains(&unit.as_str()) {
+        return Ok(None);
+    }
+    let wei = parse_units(amount.trim(), unit.as_str())
+        .map_err(|err| eyre!("invalid amount {}: {}", value, err))?;
+    Ok(Some(wei))
+}
+
+// the args with every @file:path given from dir made relative to the project
+// root, where they are read at deploy time
+pub fn relative_file_args(dir: &Path, args: &[String]) -> Result<Vec<String>> {
+    args.iter()
+        .map(|arg| {
+            let mut resolved = String::new();
+            let mut rest = arg.as_str();
+            while let Some(start) = rest.find(FILE_PREFIX) {
+                let (head, tail) = rest.split_at(start + FILE_PREFIX.len());
+                resolved.push_str(head);
+                // the path ends at the end of the item it is in
+                let end = tail
+                    .find(|c: char| [',', ']', ')', '"'].contains(&c))
+                    .unwrap_or(tail.len());
+                let (path, tail) = tail.split_at(end);
+                let path = path.trim();
+                resolved.push_str(&relative_path(dir, path)?);
+                rest = tail;
+            }
+            resolved.push_str(rest);
+            Ok(resolved)
+        })
+        .collect()
+}
 
-    parse_tokens(params, true)
+// bytes are the hex text of the file or its raw content, the other types the
+// json value of the file, or its text for a string
+fn file_arg(param: &ParamType, path: &str) -> Result<Token> {
+    let content = fs::read(path).wrap_err_with(|| format!("Failed to read {}", path))?;
+    let text = String::from_utf8(content.clone()).ok();
+    match (param, text.as_deref().map(str::trim)) {
+        (ParamType::Bytes | ParamType::FixedBytes(_), Some(hex)) if hex.starts_with("0x") => {
+            single_token(param, hex)
+        }
+        (ParamType::Bytes, _) => Ok(Token::Bytes(content)),
+        (ParamType::FixedBytes(len), _) if content.len() == *len => Ok(Token::FixedBytes(content)),
+        (ParamType::FixedBytes(len), _) => Err(eyre!(
+            "expected {} bytes in {}, got {}",
+            len,
+            path,
+            content.len()
+        )),
+        (_, Some(text)) => match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(json) => json_token(param, &json),
+            Err(_) if *param == ParamType::String => Ok(Token::String(text.to_string())),
+            Err(err) => Err(eyre!("invalid json in {}: {}", path, err)),
+        },
+        (_, None) => Err(eyre!("{} is not a text file", path)),
+    }
+}
+
+// json arrays are arrays or tuples, the other values are parsed as args
+fn json_token(param: &ParamType, json: &serde_json::Value) -> Result<Token> {
+    use serde_json::Value;
+    match (param, json) {
+        (ParamType::Array(inner), Value::Array(items)) => Ok(Token::Array(
+            items
+                .iter()
+                .map(|item| json_token(inner, item))
+                .collect::<Result<_>>()?,
+        )),
+        (ParamType::FixedArray(inner, len), Value::Array(items)) if items.len() == *len => {
+            Ok(Token::FixedArray(
+                items
+                    .iter()
+                    .map(|item| json_token(inner, item))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        (ParamType::Tuple(params), Value::Array(items)) if items.len() == params.len() => {
+            Ok(Token::Tuple(
+                params
+                    .iter()
+                    .zip(items.iter())
+                    .map(|(param, item)| json_token(param, item))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        (ParamType::String, Value::String(text)) => Ok(Token::String(text.clone())),
+        (_, Value::String(text)) => parse_arg(param, text),
+        // json numbers above u64 are floats and lose digits, those are strings
+        (ParamType::Uint(_) | ParamType::Int(_), Value::Number(number)) if number.is_f64() => {
+            Err(eyre!(
+                "json number {} is not an exact {}, write it as a string",
+                number,
+                param
+            ))
+        }
+        (_, Value::Number(number)) => parse_arg(param, &number.to_string()),
+        (_, Value::Bool(flag)) => parse_arg(param, &flag.to_string()),
+        _ => Err(eyre!("json {} is not a {}", json, param)),
+    }
+}
+
+// the items of a [..] or (..) literal, split at the commas outside nested
+// literals and quoted strings
+fn split_items(value: &str, open: char, close: char) -> Result<Vec<String>> {
+    let inner = value
+        .trim()
+        .strip_prefix(open)
+        .and_then(|value| value.strip_suffix(close))
+        .ok_or_else(|| eyre!("expected {}..{} of {}", open, close, value))?;
+    if inner.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    for c in inner.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' | '(' if !quoted => depth += 1,
+            ']' | ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                items.push(item.trim().to_string());
+                item.clear();
+                continue;
+            }
+            _ => {}
+        }
+        item.push(c);
+    }
+    items.push(item.trim().to_string());
+    Ok(items)
+}
+
+// the value without the double quotes around it
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+// how a value of the type is written, as a hint for the invalid ones
+fn param_format(param: &ParamType) -> String {
+    match param {
+        ParamType::Address => "a 0x prefixed address of 40 hex digits".to_string(),
+        ParamType::Uint(_) => {
+            "a non-negative decimal or 0x hex number, or an amount as 1.5 ether".to_string()
+        }
+        ParamType::Int(_) => "a decimal number".to_string(),
+        ParamType::Bool => "true or false".to_string(),
+        ParamType::String => "any text".to_string(),
+        ParamType::Bytes => "0x prefixed hex bytes or @file:path".to_string(),
+        ParamType::FixedBytes(len) => format!("0x prefixed hex of {} bytes", len),
+        ParamType::Array(param) | ParamType::FixedArray(param, _) => {
+            format!("[a, b, ..] of {}", param_format(param))
+        }
+        ParamType::Tuple(params) => format!(
+            "({})",
+            params
+                .iter()
+                .map(|param| param.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
 }
 
 pub fn get_from_private_key(private_key: &str) -> Result<LocalWallet> {
@@ -83,37 +472,236 @@ pub fn get_from_private_key(private_key: &str) -> Result<LocalWallet> {
     LocalWallet::from_str(privk).map_err(|x| eyre!("Failed to create wallet from private key: {x}"))
 }
 
+// http, websocket or ipc provider by the rpc url scheme, retries transient rpc
+// failures and fails over between several rpc urls
+pub async fn get_rpc_provider(
+    rpc_urls: &[String],
+    max_retries: u32,
+    quorum: usize,
+) -> Result<Provider<Transport>> {
+    let transport = Transport::connect_all(rpc_urls, max_retries, quorum).await?;
+    let provider = Provider::new(transport).interval(Duration::from_millis(10u64));
+    Ok(provider)
+}
+
+// signer of the local anvil node when the network is local, otherwise of the
+// rpc urls and private key of the network, which must be set. The node must be
+// on the chain id the network declares
 pub async fn get_provider(
-    anvil: &AnvilInstance,
-    rpc_url: String,
-    pri_key: String,
-) -> SignerMiddleware<Provider<Http>, Wallet<SigningKey>> {
-    if rpc_url.is_empty() || pri_key.is_empty() || rpc_url.contains("http://localhost:8545") {
-        let provider = Provider::<Http>::try_from(anvil.endpoint())
-            .unwrap()
-            .interval(Duration::from_millis(10u64));
-        let wallet: LocalWallet = anvil.keys()[0].clone().into();
-        let provider = SignerMiddleware::new(provider.clone(), wallet);
-        return provider;
-    }
-
-    let provider = Provider::<Http>::try_from(rpc_url)
-        .unwrap()
-        .interval(Duration::from_millis(10u64));
-    let wallet = get_from_private_key(&pri_key.as_str());
-    let chain_id = provider.get_chainid().await.unwrap();
-    let wallet = wallet.unwrap().with_chain_id(chain_id.as_u64());
-    let provider = SignerMiddleware::new(provider.clone(), wallet);
-    // Arc::new(provider)
-    provider
+    anvil: Option<&AnvilInstance>,
+    network: &Network,
+) -> Result<SignerMiddleware<Provider<Transport>, Wallet<SigningKey>>> {
+    let (provider, wallet) = match anvil {
+        Some(anvil) => {
+            let provider = get_rpc_provider(&[anvil.endpoint()], network.max_retries, 0).await?;
+            let wallet: LocalWallet = anvil.keys()[0].clone().into();
+            (provider, wallet)
+        }
+        None => {
+            let rpc_urls = network.rpc_urls();
+            if rpc_urls.is_empty() {
+                return Err(eyre!(
+                    "Rpc url not set, please set or deploy with --network local"
+                ));
+            }
+            let pri_key = network
+                .pri_key
+                .as_deref()
+                .filter(|pri_key| !pri_key.is_empty())
+                .ok_or_else(|| {
+                    eyre!("Private key not set, please set or deploy with --network local")
+                })?;
+            let provider = get_rpc_provider(&rpc_urls, network.max_retries, network.quorum).await?;
+            (provider, get_from_private_key(pri_key)?)
+        }
+    };
+    let chain_id = provider.get_chainid().await?;
+    network.check_chain_id(chain_id.as_u64())?;
+    let wallet = wallet.with_chain_id(chain_id.as_u64());
+    Ok(SignerMiddleware::new(provider, wallet))
 }
 
 #[allow(dead_code)]
 pub fn get_anvil_provider(anvil: &AnvilInstance, idx: usize) -> Provider<Http> {
     let sender = anvil.addresses()[idx];
-    let provider = Provider::<Http>::try_from(anvil.endpoint())
+    Provider::<Http>::try_from(anvil.endpoint())
         .unwrap()
         .interval(Duration::from_millis(10u64))
-        .with_sender(sender);
-    provider
+        .with_sender(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, kind: ParamType) -> Param {
+        Param {
+            name: name.to_string(),
+            kind,
+            internal_type: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_arg() {
+        let owner = "0x0000000000000000000000000000000000000001";
+        let address = Token::Address(Address::from_low_u64_be(1));
+
+        assert_eq!(
+            parse_arg(&ParamType::Uint(256), "1.5 ether").unwrap(),
+            Token::Uint(U256::exp10(18) * 3 / 2)
+        );
+        assert_eq!(
+            parse_arg(&ParamType::Uint(256), "20gwei").unwrap(),
+            Token::Uint(U256::exp10(9) * 20)
+        );
+        assert_eq!(
+            parse_arg(&ParamType::Uint(256), "\"0x10\"").unwrap(),
+            Token::Uint(16.into())
+        );
+        assert_eq!(
+            parse_arg(
+                &ParamType::Array(Box::new(ParamType::Array(Box::new(ParamType::Uint(8))))),
+                "[[1, 2], [], [3]]"
+            )
+            .unwrap(),
+            Token::Array(vec![
+                Token::Array(vec![Token::Uint(1.into()), Token::Uint(2.into())]),
+                Token::Array(vec![]),
+                Token::Array(vec![Token::Uint(3.into())]),
+            ])
+        );
+        let tuple = ParamType::Tuple(vec![
+            ParamType::Address,
+            ParamType::String,
+            ParamType::Array(Box::new(ParamType::Uint(256))),
+        ]);
+        assert_eq!(
+            parse_arg(&tuple, &format!("({}, \"a, b\", [1 gwei])", owner)).unwrap(),
+            Token::Tuple(vec![
+                address.clone(),
+                Token::String("a, b".into()),
+                Token::Array(vec![Token::Uint(U256::exp10(9))]),
+            ])
+        );
+        assert!(parse_arg(&tuple, "(1, 2)").is_err());
+        assert!(parse_arg(
+            &ParamType::FixedArray(Box::new(ParamType::Bool), 2),
+            "[true]"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_file_arg() {
+        // given
+        let dir = std::env::temp_dir().join(format!("morge-args-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let blob = dir.join("blob.bin");
+        let hex = dir.join("blob.hex");
+        let json = dir.join("owners.json");
+        fs::write(&blob, [0xde, 0xad, 0xff]).unwrap();
+        fs::write(&hex, "0xbeef\n").unwrap();
+        fs::write(
+            &json,
+            r#"[["0x0000000000000000000000000000000000000001", "2 ether"]]"#,
+        )
+        .unwrap();
+        let file = |path: &Path| format!("@file:{}", path.display());
+
+        // when
+        let raw = parse_arg(&ParamType::Bytes, &file(&blob)).unwrap();
+        let text = parse_arg(&ParamType::Bytes, &file(&hex)).unwrap();
+        let owners = parse_arg(
+            &ParamType::Array(Box::new(ParamType::Tuple(vec![
+                ParamType::Address,
+                ParamType::Uint(256),
+            ]))),
+            &file(&json),
+        )
+        .unwrap();
+
+        // then
+        assert_eq!(raw, Token::Bytes(vec![0xde, 0xad, 0xff]));
+        assert_eq!(text, Token::Bytes(vec![0xbe, 0xef]));
+        assert_eq!(
+            owners,
+            Token::Array(vec![Token::Tuple(vec![
+                Token::Address(Address::from_low_u64_be(1)),
+                Token::Uint(U256::exp10(18) * 2),
+            ])])
+        );
+        assert!(parse_arg(&ParamType::Bytes, "@file:missing.bin").is_err());
+
+        // large uints are json strings
+        let amounts = dir.join("amounts.json");
+        let uints = ParamType::Array(Box::new(ParamType::Uint(256)));
+        fs::write(&amounts, r#"[1, "1000000000000000000000"]"#).unwrap();
+        assert_eq!(
+            parse_arg(&uints, &file(&amounts)).unwrap(),
+            Token::Array(vec![Token::Uint(1.into()), Token::Uint(U256::exp10(21))])
+        );
+        fs::write(&amounts, "[1000000000000000000000]").unwrap();
+        let err = parse_arg(&uints, &file(&amounts)).unwrap_err();
+        assert!(err.to_string().contains("write it as a string"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_relative_file_args() {
+        let root = std::env::current_dir().unwrap();
+        let args = relative_file_args(
+            &root.join("examples"),
+            &[
+                "holders=@file:holders.json".into(),
+                "[@file:a.bin, @file:../b.bin]".into(),
+                "1 ether".into(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "holders=@file:examples/holders.json",
+                "[@file:examples/a.bin, @file:b.bin]",
+                "1 ether",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_named_constructor_args() {
+        let constructor = Constructor {
+            inputs: vec![
+                param("owner", ParamType::Address),
+                param("supply", ParamType::Uint(256)),
+            ],
+        };
+        let owner = "0x0000000000000000000000000000000000000001";
+
+        let tokens = parse_constructor_args(
+            Some(&constructor),
+            &["supply=1000 ether".into(), format!("owner={}", owner)],
+        )
+        .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Address(Address::from_low_u64_be(1)),
+                Token::Uint(U256::exp10(21)),
+            ]
+        );
+        assert_eq!(
+            check_constructor_args(Some(&constructor), &["supply=1".into()]),
+            vec!["missing arg address owner".to_string()]
+        );
+        assert_eq!(
+            check_constructor_args(Some(&constructor), &[owner.into(), "1 ether".into()]),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            check_constructor_args(Some(&constructor), &[owner.into(), "supply=1".into()]),
+            vec!["mix of named and positional args, 1 of 2 are named".to_string()]
+        );
+    }
 }
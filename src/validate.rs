@@ -0,0 +1,282 @@
+use reqwest::Url;
+use std::fmt;
+use std::path::Path;
+
+use crate::config::{resolve_secret, Config, Network};
+use crate::contract::ContractInfo;
+use crate::local::DUMP_STATE_UNSUPPORTED;
+use crate::project::config_file;
+use crate::transport::Scheme;
+use crate::utils::parse::{check_constructor_args, get_from_private_key};
+
+// a problem of the config, located by its file and field
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            config_file().display(),
+            self.field,
+            self.message
+        )
+    }
+}
+
+fn problem(field: impl Into<String>, message: impl Into<String>) -> Problem {
+    Problem {
+        field: field.into(),
+        message: message.into(),
+    }
+}
+
+// every problem of the config found without connecting to any node, the
+// contracts are compiled with the local solc
+pub fn validate(cfg: &Config) -> Vec<Problem> {
+    let mut problems = vec![];
+    check_url(&mut problems, "rpc_url", cfg.rpc_url.as_deref());
+    check_pri_key(&mut problems, "pri_key", cfg.pri_key.as_deref());
+    for (name, network) in cfg.networks.iter() {
+        check_network(&mut problems, &format!("networks.{}", name), network);
+    }
+    for (idx, chain) in cfg.chains.iter().enumerate() {
+        let field = format!("chains[{}] ({})", idx, chain.name);
+        check_url(
+            &mut problems,
+            &format!("{}.explorer_url", field),
+            chain.explorer_url.as_deref(),
+        );
+        if cfg.chains[..idx].iter().any(|item| item.name == chain.name) {
+            problems.push(problem(field, "chain defined more than once"));
+        }
+    }
+    for (idx, contract) in cfg.contracts.iter().enumerate() {
+        check_contract(
+            &mut problems,
+            &format!(
+                "contracts[{}] ({}:{})",
+                idx, contract.contract, contract.name
+            ),
+            contract,
+        );
+    }
+    problems
+}
+
+fn check_network(problems: &mut Vec<Problem>, field: &str, network: &Network) {
+    check_url(
+        problems,
+        &format!("{}.rpc_url", field),
+        network.rpc_url.as_deref(),
+    );
+    for (idx, rpc_url) in network.fallback_rpc_urls.iter().enumerate() {
+        check_url(
+            problems,
+            &format!("{}.fallback_rpc_urls[{}]", field, idx),
+            Some(rpc_url),
+        );
+    }
+    check_pri_key(
+        problems,
+        &format!("{}.pri_key", field),
+        network.pri_key.as_deref(),
+    );
+    check_url(
+        problems,
+        &format!("{}.explorer_url", field),
+        network.explorer_url.as_deref(),
+    );
+    if let Some(local) = &network.local {
+        if local.dump_state.is_some() {
+            problems.push(problem(
+                format!("{}.local.dump_state", field),
+                format!("{}, use morge node instead", DUMP_STATE_UNSUPPORTED),
+            ));
+        }
+    }
+    let rpc_urls = network.rpc_urls().len();
+    if network.quorum > rpc_urls {
+        problems.push(problem(
+            format!("{}.quorum", field),
+            format!(
+                "quorum of {} is more than the {} rpc urls",
+                network.quorum, rpc_urls
+            ),
+        ));
+    }
+}
+
+// http(s) and ws(s) urls must parse, ipc paths are taken as they are
+fn check_url(problems: &mut Vec<Problem>, field: &str, url: Option<&str>) {
+    let url = match url {
+        Some(url) if !url.is_empty() => url,
+        _ => return,
+    };
+    if Scheme::from_url(url) == Scheme::Ipc {
+        return;
+    }
+    match Url::parse(url) {
+        Ok(parsed) if ["http", "https", "ws", "wss"].contains(&parsed.scheme()) => {}
+        Ok(parsed) => problems.push(problem(
+            field,
+            format!("unsupported scheme {} of {}", parsed.scheme(), url),
+        )),
+        Err(err) => problems.push(problem(field, format!("invalid url {}: {}", url, err))),
+    }
+}
+
+// env:NAME keys are checked when the env is set
+fn check_pri_key(problems: &mut Vec<Problem>, field: &str, pri_key: Option<&str>) {
+    let pri_key = resolve_secret(pri_key.map(String::from)).unwrap_or_default();
+    if let Some(pri_key) = pri_key.as_deref().filter(|pri_key| !pri_key.is_empty()) {
+        if get_from_private_key(pri_key).is_err() {
+            problems.push(problem(field, "invalid private key"));
+        }
+    }
+}
+
+fn check_contract(problems: &mut Vec<Problem>, field: &str, contract: &ContractInfo) {
+    let path = Path::new(&contract.contract);
+    if !path.exists() {
+        problems.push(problem(
+            format!("{}.contract", field),
+            format!("file {} not found", contract.contract),
+        ));
+        return;
+    }
+    if path.extension().and_then(|ext| ext.to_str()) != Some("sol") {
+        problems.push(problem(
+            format!("{}.contract", field),
+            format!("{} is not a sol file", contract.contract),
+        ));
+        return;
+    }
+
+    let output = match contract.compiler.compile(&contract.contract) {
+        Ok(output) => output,
+        Err(err) => {
+            problems.push(problem(
+                format!("{}.contract", field),
+                format!("compile failed: {}", err),
+            ));
+            return;
+        }
+    };
+    if output.has_error() {
+        let errors = output
+            .errors
+            .iter()
+            .filter(|error| error.severity.is_error())
+            .map(|error| error.message.clone())
+            .collect::<Vec<_>>();
+        problems.push(problem(
+            format!("{}.contract", field),
+            format!("compile failed: {}", errors.join("; ")),
+        ));
+        return;
+    }
+    let abi = match output.find(&contract.name).and_then(|found| found.abi) {
+        Some(abi) => abi,
+        None => {
+            problems.push(problem(
+                format!("{}.name", field),
+                format!(
+                    "contract {} not found in {}",
+                    contract.name, contract.contract
+                ),
+            ));
+            return;
+        }
+    };
+    for message in check_constructor_args(abi.constructor.as_ref(), &contract.args) {
+        problems.push(problem(format!("{}.args", field), message));
+    }
+    let compiled = ContractInfo {
+        abi: abi.clone(),
+        ..contract.clone()
+    };
+    for (idx, call) in contract.calls.iter().enumerate() {
+        if let Err(err) = compiled.calldata(call) {
+            problems.push(problem(
+                format!("{}.calls[{}]", field, idx),
+                err.to_string(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ChainInfo;
+
+    #[test]
+    fn test_validate() {
+        // given
+        let mut cfg = Config {
+            rpc_url: Some("http//localhost:8545".into()),
+            pri_key: Some("0xnotakey".into()),
+            ..Default::default()
+        };
+        cfg.networks.insert(
+            "polygon".into(),
+            Network {
+                rpc_url: Some("https://polygon-rpc.com".into()),
+                fallback_rpc_urls: vec!["ftp://polygon.io".into()],
+                quorum: 3,
+                ..Default::default()
+            },
+        );
+        cfg.chains = vec![ChainInfo::new("base", 8453), ChainInfo::new("base", 8453)];
+        cfg.contracts = vec![
+            ContractInfo::new("examples/contract.sol:SimpleStorage".into(), vec![]),
+            ContractInfo::new("examples/contract.sol:Missing".into(), vec![]),
+            ContractInfo::new("examples/missing.sol:SimpleStorage".into(), vec![]),
+        ];
+
+        // when
+        let problems = validate(&cfg);
+
+        // then every problem is reported at once
+        let fields = problems
+            .iter()
+            .map(|problem| problem.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                "rpc_url",
+                "pri_key",
+                "networks.polygon.fallback_rpc_urls[0]",
+                "networks.polygon.quorum",
+                "chains[1] (base)",
+                "contracts[0] (examples/contract.sol:SimpleStorage).args",
+                "contracts[1] (examples/contract.sol:Missing).name",
+                "contracts[2] (examples/missing.sol:SimpleStorage).contract",
+            ]
+        );
+        assert!(problems[5].message.contains("expected 1 constructor args"));
+        assert!(problems[0].to_string().starts_with("morge.toml: rpc_url: "));
+    }
+
+    #[test]
+    fn test_validate_valid_config() {
+        let mut cfg = Config {
+            rpc_url: Some("ws://localhost:8546".into()),
+            pri_key: Some(
+                "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80".into(),
+            ),
+            ..Default::default()
+        };
+        cfg.contracts = vec![ContractInfo::new(
+            "examples/contract.sol:SimpleStorage".into(),
+            vec!["value".into()],
+        )];
+        assert_eq!(validate(&cfg), vec![]);
+    }
+}
@@ -1,35 +1,151 @@
-use core::panic;
+use ethers::abi::{decode, ParamType};
 use ethers::prelude::*;
+use ethers::utils::hex;
 
-use crate::{CHAINS_MAP, KEYS_MAP};
+use crate::chain::{amount, ChainInfo, ChainRegistry};
+use crate::explorer::Explorer;
+use crate::journal::Deployment;
+
+// selectors of Error(string) and Panic(uint256) revert data
+static ERROR_SELECTOR: &str = "08c379a0";
+static PANIC_SELECTOR: &str = "4e487b71";
 
 pub struct Verify;
 
+// transaction result read from the node
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxReport {
+    pub hash: H256,
+    pub success: bool,
+    pub block_number: u64,
+    pub confirmations: u64,
+    pub gas_used: U256,
+    pub effective_gas_price: Option<U256>,
+    pub contract_address: Option<Address>,
+    pub revert_reason: Option<String>,
+}
+
+// verification result of a journaled deployment
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordStatus {
+    Verified,
+    Failed(String),
+    Missing(String),
+}
+
 impl Verify {
-    fn get_chainnet(chain: &str) -> Chain {
-        match CHAINS_MAP.contains_key(chain) {
-            true => CHAINS_MAP.get(chain).unwrap().clone(),
-            false => Chain::Mainnet,
+    // read the receipt from the node, no explorer is needed
+    pub async fn verify_tx_by_rpc<M: Middleware>(client: &M, tx: H256) -> eyre::Result<TxReport>
+    where
+        M::Error: 'static,
+    {
+        let receipt = match client.get_transaction_receipt(tx).await? {
+            Some(receipt) => receipt,
+            None => match client.get_transaction(tx).await? {
+                Some(_) => return Err(eyre::eyre!("Transaction {:?} is pending", tx)),
+                None => return Err(eyre::eyre!("Transaction {:?} not found", tx)),
+            },
+        };
+        let block_number = receipt.block_number.unwrap_or_default().as_u64();
+        let current = client.get_block_number().await?.as_u64();
+        let success = receipt.status == Some(1.into());
+        let revert_reason = match success {
+            true => None,
+            false => Verify::revert_reason(client, tx, block_number).await?,
+        };
+
+        Ok(TxReport {
+            hash: tx,
+            success,
+            block_number,
+            confirmations: current.saturating_sub(block_number) + 1,
+            gas_used: receipt.gas_used.unwrap_or_default(),
+            effective_gas_price: receipt.effective_gas_price,
+            contract_address: receipt.contract_address,
+            revert_reason,
+        })
+    }
+
+    // the deployment transaction is mined successfully and created the
+    // recorded contract, which still has code
+    pub async fn verify_deployment<M: Middleware>(
+        client: &M,
+        deployment: &Deployment,
+    ) -> RecordStatus
+    where
+        M::Error: 'static,
+    {
+        let report = match Verify::verify_tx_by_rpc(client, deployment.tx_hash).await {
+            Ok(report) => report,
+            Err(err) => return RecordStatus::Missing(err.to_string()),
+        };
+        if !report.success {
+            return RecordStatus::Failed(format!(
+                "transaction reverted: {}",
+                report.revert_reason.unwrap_or_default()
+            ));
+        }
+        if report.contract_address != Some(deployment.address) {
+            return RecordStatus::Failed(format!(
+                "transaction created {:?}",
+                report.contract_address.unwrap_or_default()
+            ));
+        }
+        match client.get_code(deployment.address, None).await {
+            Ok(code) if code.as_ref().is_empty() => RecordStatus::Missing("no code".to_string()),
+            Ok(_) => RecordStatus::Verified,
+            Err(err) => RecordStatus::Missing(err.to_string()),
         }
     }
 
-    fn get_api_key(chain: &str) -> &str {
-        match KEYS_MAP.contains_key(chain) {
-            true => KEYS_MAP.get(chain).unwrap().clone(),
-            false => panic!("{} chain api key not found", chain),
+    // replay the failed transaction on the state before its block
+    async fn revert_reason<M: Middleware>(
+        client: &M,
+        tx: H256,
+        block_number: u64,
+    ) -> eyre::Result<Option<String>>
+    where
+        M::Error: 'static,
+    {
+        let tx = match client.get_transaction(tx).await? {
+            Some(tx) => tx,
+            None => return Ok(None),
+        };
+        let mut call = TransactionRequest::new()
+            .from(tx.from)
+            .data(tx.input)
+            .value(tx.value)
+            .gas(tx.gas);
+        if let Some(to) = tx.to {
+            call = call.to(to);
+        }
+        let block = BlockId::from(block_number.saturating_sub(1));
+        match client.call(&call.into(), Some(block)).await {
+            Ok(_) => Ok(None),
+            Err(err) => Ok(decode_revert_reason(&err.to_string())),
         }
     }
 
+    // check the transaction status on the explorer of the chain, built-in or
+    // user-defined
     pub async fn verify_tx(chain: &str, tx: &str) -> eyre::Result<bool> {
-        let chainnet = Verify::get_chainnet(chain);
-        let key = Verify::get_api_key(chain);
-        let client = Client::new(chainnet, key).unwrap();
+        let chains = ChainRegistry::load();
+        let chain = chains.by_name(chain).ok_or_else(|| {
+            eyre::eyre!(
+                "Unknown chain {}, set it with set-chain or verify by the rpc url of a network",
+                chain
+            )
+        })?;
+        let explorer = Explorer::for_chain(chain)?;
 
-        let status = client.check_transaction_receipt_status(tx).await;
-        match status {
-            Ok(_) => {
+        match explorer.tx_status(tx).await {
+            Ok(true) => {
                 println!("Verify tx: {} status success", tx);
-                return Ok(true);
+                Ok(true)
+            }
+            Ok(false) => {
+                println!("Verify tx: {} failed or not found", tx);
+                Ok(false)
             }
             Err(err) => {
                 println!("Verify tx: {} failed, err: {:?}", tx, err);
@@ -39,12 +155,190 @@ impl Verify {
     }
 }
 
+// the fee is reported in the native currency of the chain if it is known
+pub fn report_tx(report: &TxReport, chain: Option<&ChainInfo>) {
+    println!("Transaction hash: {:?}", report.hash);
+    println!(
+        "Status: {}",
+        match report.success {
+            true => "success",
+            false => "failed",
+        }
+    );
+    println!("Block number: {}", report.block_number);
+    println!("Confirmations: {}", report.confirmations);
+    println!("Gas used: {}", report.gas_used);
+    if let Some(effective_gas_price) = report.effective_gas_price {
+        println!("Effective gas price: {}", effective_gas_price);
+        println!(
+            "Fee: {}",
+            amount(chain, report.gas_used * effective_gas_price)
+        );
+    }
+    if let Some(contract_address) = report.contract_address {
+        println!("Contract address: {:?}", contract_address);
+    }
+    if let Some(revert_reason) = &report.revert_reason {
+        println!("Revert reason: {}", revert_reason);
+    }
+}
+
+// decode the Error(string) or Panic(uint256) revert data in the rpc error,
+// or take the reason the node has decoded already
+fn decode_revert_reason(err: &str) -> Option<String> {
+    let revert_data = |selector: &str| {
+        let start = err.find(&format!("0x{}", selector))? + 2;
+        let data = err[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_hexdigit())
+            .collect::<String>();
+        hex::decode(&data[8..]).ok()
+    };
+
+    if let Some(data) = revert_data(ERROR_SELECTOR) {
+        if let Ok(tokens) = decode(&[ParamType::String], &data) {
+            return tokens
+                .into_iter()
+                .next()
+                .and_then(|token| token.into_string());
+        }
+    }
+    if let Some(data) = revert_data(PANIC_SELECTOR) {
+        if let Ok(tokens) = decode(&[ParamType::Uint(256)], &data) {
+            return tokens
+                .into_iter()
+                .next()
+                .and_then(|token| token.into_uint())
+                .map(|code| format!("panic code {:#x}", code));
+        }
+    }
+    let start = err.find("execution reverted: ")? + "execution reverted: ".len();
+    let reason = err[start..].split(", data:").next()?;
+    Some(reason.trim_end_matches(')').to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Network;
+    use crate::contract::ContractInfo;
+    use crate::nonce::{NonceManager, ReplaceOptions};
+    use crate::project::TempRoot;
+    use crate::utils::parse::get_provider;
+    use ethers::utils::Anvil;
+
+    // init code which reverts with Error("nope")
+    fn reverting_code() -> Bytes {
+        let code = "6064600c60003960646000fd".to_string()
+            + ERROR_SELECTOR
+            + "0000000000000000000000000000000000000000000000000000000000000020"
+            + "0000000000000000000000000000000000000000000000000000000000000004"
+            + "6e6f706500000000000000000000000000000000000000000000000000000000";
+        hex::decode(code).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_verify_deployment() {
+        // given
+        let _root = TempRoot::new();
+        let anvil = Anvil::new().spawn();
+        let client = get_provider(Some(&anvil), &Network::default())
+            .await
+            .unwrap();
+        let mut manager = NonceManager::new(client, ReplaceOptions::default());
+        let mut contract = ContractInfo::new(
+            "examples/contract.sol:SimpleStorage".to_string(),
+            vec!["value".into()],
+        );
+        let receipt = contract.run(&mut manager).await.unwrap();
+        let deployment = Deployment::new(
+            "default",
+            31337,
+            &contract.contract,
+            &contract.name,
+            &receipt,
+            1,
+        );
+
+        // when
+        let client = manager.client();
+        let verified = Verify::verify_deployment(client, &deployment).await;
+        let mut moved = deployment.clone();
+        moved.address = Address::random();
+        let failed = Verify::verify_deployment(client, &moved).await;
+        let mut missing = deployment.clone();
+        missing.tx_hash = H256::random();
+        let missing = Verify::verify_deployment(client, &missing).await;
+
+        // then
+        assert_eq!(verified, RecordStatus::Verified);
+        assert!(matches!(failed, RecordStatus::Failed(_)));
+        assert!(matches!(missing, RecordStatus::Missing(_)));
+    }
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let err = format!(
+            "(code: 3, message: execution reverted: nope, data: Some(String(\"0x{}\")))",
+            hex::encode(&reverting_code().as_ref()[12..])
+        );
+        assert_eq!(decode_revert_reason(&err), Some("nope".into()));
+        assert_eq!(
+            decode_revert_reason("(code: 3, message: execution reverted: not owner, data: None)"),
+            Some("not owner".into())
+        );
+        assert_eq!(decode_revert_reason("nonce too low"), None);
+    }
+
+    #[tokio::test]
+    async fn test_verify_tx_by_rpc() {
+        // given
+        let anvil = Anvil::new().spawn();
+        let client = get_provider(Some(&anvil), &Network::default())
+            .await
+            .unwrap();
+        let to = client.address();
+
+        // when
+        let success = client
+            .send_transaction(TransactionRequest::new().to(to).value(1u64), None)
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .unwrap();
+        let failed = client
+            .send_transaction(
+                TransactionRequest::new()
+                    .data(reverting_code())
+                    .gas(100_000u64),
+                None,
+            )
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .unwrap();
+
+        // then
+        let report = Verify::verify_tx_by_rpc(&client, success.transaction_hash)
+            .await
+            .unwrap();
+        assert!(report.success);
+        assert_eq!(report.confirmations, 2);
+        assert_eq!(report.gas_used, 21000.into());
+        let report = Verify::verify_tx_by_rpc(&client, failed.transaction_hash)
+            .await
+            .unwrap();
+        assert!(!report.success);
+        assert_eq!(report.revert_reason, Some("nope".into()));
+        assert!(Verify::verify_tx_by_rpc(&client, H256::random())
+            .await
+            .is_err());
+    }
 
     #[tokio::test]
-    // #[ignore = "maybe failed due to China gov firewall"]
+    #[ignore = "needs the explorer api key in ETHERSCAN_API_KEY and network access, run with --ignored"]
     async fn test_rinkeby_verify() {
         let chain = "rinkeby";
         let existed_tx = "0xc6e08d3b5b1077f4662907fa547fab34bac033a0501655aca0b903057c118da8";
@@ -57,7 +351,7 @@ mod tests {
     }
 
     #[tokio::test]
-    // #[ignore = "maybe failed due to China gov firewall"]
+    #[ignore = "needs the explorer api key in FTMSCAN_API_KEY and network access, run with --ignored"]
     async fn test_fantom_verify() {
         let chain = "fantom";
         let existed_tx = "0xb4b8b03c36ff4d6668c7aab2c78a4936b3ac79dda5e07cf7e509c01680fea443";
@@ -70,7 +364,7 @@ mod tests {
     }
 
     #[tokio::test]
-    // #[ignore = "maybe failed due to China gov firewall"]
+    #[ignore = "needs the explorer api key in POLYGONSCAN_API_KEY and network access, run with --ignored"]
     async fn test_polygon_verify() {
         let chain = "polygon";
         let existed_tx = "0x803aa2410fb9976c432e5390728798c98a8b4dda4ef694e7dab79f25cdffcdd6";
@@ -83,7 +377,7 @@ mod tests {
     }
 
     #[tokio::test]
-    // #[ignore = "maybe failed due to China gov firewall"]
+    #[ignore = "needs the explorer api key in BSCSCAN_API_KEY and network access, run with --ignored"]
     async fn test_bsc_verify() {
         let chain = "bsc";
         let existed_tx = "0x98821751920196f1c5919635b7c371af7adfac2c6f7be2d832aae39f303b2406";
@@ -96,7 +390,7 @@ mod tests {
     }
 
     #[tokio::test]
-    // #[ignore = "maybe failed due to China gov firewall"]
+    #[ignore = "needs the explorer api key in ARBISCAN_API_KEY and network access, run with --ignored"]
     async fn test_arbitrum_verify() {
         let chain = "arbitrum";
         let existed_tx = "0xc75a5d7ffccd3b8fc00a124a683b7e768e1a4d6a17a5977ed931965886109bf5";
@@ -109,7 +403,7 @@ mod tests {
     }
 
     #[tokio::test]
-    // #[ignore = "maybe failed due to China gov firewall"]
+    #[ignore = "needs the explorer api key in OPTIMISTIC_ETHERSCAN_API_KEY and network access, run with --ignored"]
     async fn test_optimism_verify() {
         let chain = "optimism";
         let existed_tx = "0x319c40f66639e3f1a5954621f93ad19b78043d0d8a5bbf65e20fa7f8929afd03";
@@ -122,7 +416,7 @@ mod tests {
     }
 
     #[tokio::test]
-    // #[ignore = "maybe failed due to China gov firewall"]
+    #[ignore = "needs the explorer api key in SNOWTRACE_API_KEY and network access, run with --ignored"]
     async fn test_avalanche_verify() {
         let chain = "avalanche";
         let existed_tx = "0x4a9eeaeef2990af4aa915e1433e28bf6f45b04b96f29c3c09cf6f1b79f5bfd5c";